@@ -0,0 +1,6 @@
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(dev)");
+    if std::env::var_os("CARGO_FEATURE_CUSTOM_PROTOCOL").is_none() {
+        println!("cargo:rustc-cfg=dev");
+    }
+}
@@ -0,0 +1,170 @@
+use crate::uv::requirement_name;
+
+/// What went wrong in a failed uv invocation, classified from its output so the
+/// frontend can offer a targeted fix.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum UvFailure {
+    NoMatchingDistribution { package: Option<String> },
+    ResolutionConflict { packages: Vec<String> },
+    NetworkFailure { url: Option<String> },
+    PermissionDenied { path: Option<String> },
+    DiskFull { path: Option<String> },
+    Unknown,
+}
+
+const NETWORK_ERRORS: &[&str] = &[
+    "error sending request",
+    "failed to fetch",
+    "failed to download",
+    "dns error",
+    "tcp connect error",
+    "connection refused",
+    "connection reset",
+    "network is unreachable",
+    "operation timed out",
+    "invalid peer certificate",
+];
+const PERMISSION_ERRORS: &[&str] = &["permission denied", "access is denied", "(os error 13)"];
+const DISK_FULL_ERRORS: &[&str] = &[
+    "no space left on device",
+    "not enough space on the disk",
+    "(os error 28)",
+    "(os error 112)",
+];
+/// Words that follow "Because" or "on" in resolver output without being package names.
+const RESOLVER_WORDS: &[&str] = &["there", "only", "all", "your", "no", "we", "the", "you"];
+
+impl UvFailure {
+    /// Classifies the stderr of a failed uv command.
+    pub fn classify(stderr: &str) -> Self {
+        let lower = stderr.to_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+        let quoted = backticked(stderr);
+
+        if contains_any(DISK_FULL_ERRORS) {
+            return UvFailure::DiskFull {
+                path: quoted.into_iter().find(|s| !is_url(s)),
+            };
+        }
+        if contains_any(PERMISSION_ERRORS) {
+            return UvFailure::PermissionDenied {
+                path: quoted.into_iter().find(|s| !is_url(s)),
+            };
+        }
+        if contains_any(NETWORK_ERRORS) {
+            return UvFailure::NetworkFailure {
+                url: quoted.into_iter().find(|s| is_url(s)),
+            };
+        }
+        if let Some(package) = missing_package(stderr) {
+            return UvFailure::NoMatchingDistribution {
+                package: Some(package),
+            };
+        }
+        if lower.contains("no solution found") || lower.contains("unsatisfiable") {
+            return UvFailure::ResolutionConflict {
+                packages: conflicting_packages(stderr),
+            };
+        }
+        UvFailure::Unknown
+    }
+
+    /// Classifies an error that happened before uv produced any output.
+    pub fn from_io_error(err: &std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied => UvFailure::PermissionDenied { path: None },
+            _ => Self::classify(&err.to_string()),
+        }
+    }
+}
+
+fn is_url(text: &str) -> bool {
+    text.starts_with("http://") || text.starts_with("https://")
+}
+
+fn backticked(text: &str) -> Vec<String> {
+    text.split('`')
+        .skip(1)
+        .step_by(2)
+        .map(str::to_string)
+        .collect()
+}
+
+fn missing_package(stderr: &str) -> Option<String> {
+    if let Some((_, rest)) = stderr.split_once("there is no version of ") {
+        return first_package(rest);
+    }
+    let (before, _) = stderr.split_once(" was not found in the package registry")?;
+    first_package(before.rsplit(char::is_whitespace).next()?)
+}
+
+/// Collects the packages named in uv's resolver explanation, which reads like
+/// "Because a==1 depends on b==2 and you require a==1, ...".
+fn conflicting_packages(stderr: &str) -> Vec<String> {
+    let words = stderr.split_whitespace().collect::<Vec<_>>();
+    let mut packages = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let before = i.checked_sub(1).map(|i| words[i]);
+        let after = words.get(i + 1).copied();
+        let named = matches!(before, Some("Because" | "because" | "on" | "require"))
+            || after == Some("depends");
+        if let Some(package) = named.then(|| first_package(word)).flatten() {
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+    packages
+}
+
+fn first_package(text: &str) -> Option<String> {
+    let name = requirement_name(text.split_whitespace().next()?)?;
+    if RESOLVER_WORDS.contains(&name.as_str()) {
+        return None;
+    }
+    Some(name)
+}
+
+#[test]
+fn test_classify() {
+    assert_eq!(
+        UvFailure::classify(
+            "  × No solution found when resolving dependencies:
+  ╰─▶ Because there is no version of omu-chat==9.9.9 and you require omu-chat==9.9.9, we can conclude that your requirements are unsatisfiable."
+        ),
+        UvFailure::NoMatchingDistribution {
+            package: Some("omu-chat".to_string())
+        }
+    );
+    assert_eq!(
+        UvFailure::classify(
+            "  × No solution found when resolving dependencies:
+  ╰─▶ Because omuserver==0.10.2 depends on aiohttp==3.9.0 and plugin-foo==1.0 depends on aiohttp>=3.10, we can conclude that omuserver==0.10.2 and plugin-foo==1.0 are incompatible."
+        ),
+        UvFailure::ResolutionConflict {
+            packages: vec![
+                "omuserver".to_string(),
+                "aiohttp".to_string(),
+                "plugin-foo".to_string(),
+            ]
+        }
+    );
+    assert_eq!(
+        UvFailure::classify(
+            "error: Failed to fetch: `https://pypi.org/simple/omuserver/`
+  Caused by: error sending request for url (https://pypi.org/simple/omuserver/)"
+        ),
+        UvFailure::NetworkFailure {
+            url: Some("https://pypi.org/simple/omuserver/".to_string())
+        }
+    );
+    assert_eq!(
+        UvFailure::classify(
+            "error: failed to write to file `/opt/python/lib/site.py`: No space left on device (os error 28)"
+        ),
+        UvFailure::DiskFull {
+            path: Some("/opt/python/lib/site.py".to_string())
+        }
+    );
+}
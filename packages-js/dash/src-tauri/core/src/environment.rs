@@ -0,0 +1,195 @@
+use std::{ffi::OsString, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+/// Variables passed through from the dashboard's environment. Anything else,
+/// in particular `PYTHONPATH`, `PYTHONHOME`, `VIRTUAL_ENV` and conda's
+/// variables, is left out so it cannot leak into the managed interpreter.
+const PASSTHROUGH: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "USERNAME",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "LANGUAGE",
+    "TZ",
+    "TERM",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    // Proxies and CA bundles of corporate networks, matched case-insensitively
+    // so `no_proxy` and friends pass as well.
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "ALL_PROXY",
+    "NO_PROXY",
+    "SSL_CERT_FILE",
+    "SSL_CERT_DIR",
+    "REQUESTS_CA_BUNDLE",
+    "CURL_CA_BUNDLE",
+    // Plugins that open windows or talk to the desktop session.
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XAUTHORITY",
+    "DBUS_SESSION_BUS_ADDRESS",
+    // Windows processes fail in odd ways without these.
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "USERPROFILE",
+    "HOMEDRIVE",
+    "HOMEPATH",
+    "PROGRAMDATA",
+    "PROGRAMFILES",
+    "PROGRAMFILES(X86)",
+    "COMMONPROGRAMFILES",
+    "NUMBER_OF_PROCESSORS",
+    "PROCESSOR_ARCHITECTURE",
+];
+const PASSTHROUGH_PREFIXES: &[&str] = &["LC_", "XDG_"];
+
+/// Resource limits applied to a process, only supported on Linux.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Address space limit, `RLIMIT_AS`.
+    pub max_memory_mb: Option<u64>,
+    /// `RLIMIT_NOFILE`.
+    pub max_open_files: Option<u64>,
+}
+
+/// Environment of the processes started from the managed Python and uv.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessEnv {
+    limits: ResourceLimits,
+}
+
+impl ProcessEnv {
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The allowed subset of `vars` along with the variables we always set.
+    fn filter(vars: impl Iterator<Item = (OsString, OsString)>) -> Vec<(OsString, OsString)> {
+        let mut vars = vars
+            .filter(|(key, _)| {
+                // Windows variable names are case-insensitive.
+                let key = key.to_string_lossy().to_uppercase();
+                PASSTHROUGH.contains(&key.as_str())
+                    || PASSTHROUGH_PREFIXES
+                        .iter()
+                        .any(|prefix| key.starts_with(prefix))
+            })
+            .collect::<Vec<_>>();
+        vars.push(("PYTHONNOUSERSITE".into(), "1".into()));
+        vars.push(("PYTHONUTF8".into(), "1".into()));
+        vars
+    }
+
+    /// Replaces the environment of `cmd`, so it must be applied before any
+    /// variables specific to the command are set.
+    pub fn apply(&self, cmd: &mut Command) {
+        cmd.env_clear();
+        cmd.envs(Self::filter(std::env::vars_os()));
+        self.limits.apply(cmd);
+    }
+}
+
+impl ResourceLimits {
+    #[cfg(target_os = "linux")]
+    fn apply(&self, cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let limits = [
+            (
+                libc::RLIMIT_AS,
+                self.max_memory_mb.map(|mb| mb.saturating_mul(1024 * 1024)),
+            ),
+            (libc::RLIMIT_NOFILE, self.max_open_files),
+        ];
+        if limits.iter().all(|(_, limit)| limit.is_none()) {
+            return;
+        }
+        // SAFETY: setrlimit is async-signal-safe and the closure allocates nothing.
+        unsafe {
+            cmd.pre_exec(move || {
+                for (resource, limit) in limits {
+                    let Some(limit) = limit else {
+                        continue;
+                    };
+                    let rlimit = libc::rlimit {
+                        rlim_cur: limit as libc::rlim_t,
+                        rlim_max: limit as libc::rlim_t,
+                    };
+                    if libc::setrlimit(resource, &rlimit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn apply(&self, _cmd: &mut Command) {
+        if *self != Self::default() {
+            log::warn!("Resource limits are only supported on Linux");
+        }
+    }
+}
+
+#[test]
+fn test_filter() {
+    let vars = [
+        ("PATH", "/usr/bin"),
+        ("Path", "C:\\Windows"),
+        ("LC_ALL", "C.UTF-8"),
+        ("DISPLAY", ":0"),
+        ("WAYLAND_DISPLAY", "wayland-0"),
+        ("REQUESTS_CA_BUNDLE", "/etc/ssl/corp.pem"),
+        ("SSL_CERT_FILE", "/etc/ssl/corp.pem"),
+        ("SSL_CERT_DIR", "/etc/ssl/certs"),
+        ("no_proxy", "localhost"),
+        ("SystemRoot", "C:\\Windows"),
+        ("ComSpec", "C:\\Windows\\system32\\cmd.exe"),
+        ("PYTHONPATH", "/home/user/lib"),
+        ("PYTHONHOME", "/opt/python"),
+        ("VIRTUAL_ENV", "/home/user/.venv"),
+        ("CONDA_PREFIX", "/opt/conda"),
+        ("PYTHONUTF8", "0"),
+    ];
+    let filtered = ProcessEnv::filter(
+        vars.iter()
+            .map(|(key, value)| (OsString::from(key), OsString::from(value))),
+    );
+    let keys = filtered
+        .iter()
+        .map(|(key, _)| key.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        vec![
+            "PATH",
+            "Path",
+            "LC_ALL",
+            "DISPLAY",
+            "WAYLAND_DISPLAY",
+            "REQUESTS_CA_BUNDLE",
+            "SSL_CERT_FILE",
+            "SSL_CERT_DIR",
+            "no_proxy",
+            "SystemRoot",
+            "ComSpec",
+            "PYTHONNOUSERSITE",
+            "PYTHONUTF8"
+        ]
+    );
+    assert_eq!(filtered.last().unwrap().1, "1");
+}
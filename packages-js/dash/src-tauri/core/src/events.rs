@@ -0,0 +1,44 @@
+use serde::Serialize;
+
+use crate::{
+    logs::LogLine,
+    server::{ServerAddress, ServerState, ServerStats},
+};
+
+/// Something the supervisor of a server reports while it runs.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ServerEvent {
+    State(ServerState),
+    Log(LogLine),
+    Stats(ServerStats),
+    /// The server moved to another port.
+    Address(ServerAddress),
+}
+
+impl ServerEvent {
+    /// Name the dashboard emits the event under.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::State(_) => "server_state",
+            ServerEvent::Log(_) => "server_log",
+            ServerEvent::Stats(_) => "server_stats",
+            ServerEvent::Address(_) => "server_address",
+        }
+    }
+}
+
+/// Receives the events of a server, e.g. to forward them to a UI.
+///
+/// Events are sent from the supervisor's threads, so implementations should
+/// return quickly.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: ServerEvent);
+}
+
+/// Drops every event, for callers that only need the logs.
+pub struct NoEvents;
+
+impl EventSink for NoEvents {
+    fn emit(&self, _event: ServerEvent) {}
+}
@@ -0,0 +1,20 @@
+//! Provisioning and supervision of omuserver: downloads Python and uv,
+//! installs the server and its plugins, then runs and watches it.
+//!
+//! Nothing here depends on Tauri; progress goes through callbacks and
+//! server events through an [`events::EventSink`].
+
+pub mod diagnostics;
+pub mod environment;
+pub mod events;
+pub mod index;
+pub mod logs;
+pub mod options;
+pub mod process;
+pub mod python;
+pub mod server;
+pub mod sources;
+pub mod sync;
+pub mod utils;
+pub mod uv;
+pub mod version;
@@ -0,0 +1,231 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{index::PackageIndex, options::log_dir};
+
+/// Lines of server output kept in memory.
+pub const SERVER_LOG_LINES: usize = 5000;
+
+/// Levels of loguru, which omuserver logs with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Success,
+    Warning,
+    Error,
+    Critical,
+}
+
+impl LogLevel {
+    /// Reads the level of a line in loguru's default format,
+    /// `2024-01-01 12:00:00.000 | INFO     | module:function:1 - message`.
+    fn parse(line: &str) -> Option<Self> {
+        let level = line.split(" | ").nth(1)?;
+        Some(match level.trim() {
+            "TRACE" => LogLevel::Trace,
+            "DEBUG" => LogLevel::Debug,
+            "INFO" => LogLevel::Info,
+            "SUCCESS" => LogLevel::Success,
+            "WARNING" => LogLevel::Warning,
+            "ERROR" => LogLevel::Error,
+            "CRITICAL" => LogLevel::Critical,
+            _ => return None,
+        })
+    }
+
+    pub fn as_log_level(self) -> log::Level {
+        match self {
+            LogLevel::Trace => log::Level::Trace,
+            LogLevel::Debug => log::Level::Debug,
+            LogLevel::Info | LogLevel::Success => log::Level::Info,
+            LogLevel::Warning => log::Level::Warn,
+            LogLevel::Error | LogLevel::Critical => log::Level::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    /// Increases by one per line, so clients can ask for what they missed.
+    pub seq: u64,
+    pub time: String,
+    pub stream: LogStream,
+    pub level: LogLevel,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogFilter {
+    pub min_level: Option<LogLevel>,
+    pub stream: Option<LogStream>,
+    pub contains: Option<String>,
+    /// Only lines with a `seq` of at least this.
+    pub since: Option<u64>,
+    /// Only the last lines matching the rest of the filter.
+    pub limit: Option<usize>,
+}
+
+impl LogFilter {
+    fn matches(&self, line: &LogLine) -> bool {
+        self.min_level.map_or(true, |level| line.level >= level)
+            && self.stream.map_or(true, |stream| line.stream == stream)
+            && self.since.map_or(true, |since| line.seq >= since)
+            && self
+                .contains
+                .as_ref()
+                .map_or(true, |text| line.line.contains(text.as_str()))
+    }
+}
+
+struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    next_seq: u64,
+    /// Level of the last line per stream, for continuation lines such as tracebacks.
+    last_level: [LogLevel; 2],
+    file: Option<File>,
+    /// Index of the current run, whose credentials are redacted from every line.
+    index: Option<PackageIndex>,
+}
+
+/// Output of the server, kept across restarts and mirrored to a file per run.
+pub struct ServerLogs {
+    capacity: usize,
+    buffer: Mutex<LogBuffer>,
+}
+
+impl ServerLogs {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            buffer: Mutex::new(LogBuffer {
+                lines: VecDeque::new(),
+                next_seq: 0,
+                last_level: [LogLevel::Info; 2],
+                file: None,
+                index: None,
+            }),
+        }
+    }
+
+    /// Starts the log file of a new server run in today's log directory.
+    pub fn start_run(&self, workdir: &Path, index: &PackageIndex) -> std::io::Result<PathBuf> {
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.file = None;
+            buffer.index = Some(index.clone());
+            buffer.last_level = [LogLevel::Info; 2];
+        }
+        let dir = log_dir(workdir);
+        std::fs::create_dir_all(&dir)?;
+        let name = chrono::Local::now().format("%H-%M-%S-server-output.log");
+        let path = dir.join(name.to_string());
+        let file = File::create(&path)?;
+        self.buffer.lock().unwrap().file = Some(file);
+        Ok(path)
+    }
+
+    /// The `seq` the next line will get.
+    pub fn next_seq(&self) -> u64 {
+        self.buffer.lock().unwrap().next_seq
+    }
+
+    pub fn push(&self, stream: LogStream, line: String) -> LogLine {
+        let now = chrono::Local::now();
+        let mut buffer = self.buffer.lock().unwrap();
+        let line = match &buffer.index {
+            Some(index) => index.redact(&line),
+            None => line,
+        };
+        let level = match LogLevel::parse(&line) {
+            Some(level) => level,
+            None => buffer.last_level[stream as usize],
+        };
+        buffer.last_level[stream as usize] = level;
+        let line = LogLine {
+            seq: buffer.next_seq,
+            time: now.to_rfc3339(),
+            stream,
+            level,
+            line,
+        };
+        buffer.next_seq += 1;
+        if let Some(file) = buffer.file.as_mut() {
+            let prefix = match stream {
+                LogStream::Stdout => "",
+                LogStream::Stderr => "[stderr] ",
+            };
+            if let Err(err) = writeln!(file, "{}{}", prefix, line.line) {
+                log::warn!("Failed to write server log: {}", err);
+                buffer.file = None;
+            }
+        }
+        if buffer.lines.len() == self.capacity {
+            buffer.lines.pop_front();
+        }
+        buffer.lines.push_back(line.clone());
+        line
+    }
+
+    pub fn query(&self, filter: &LogFilter) -> Vec<LogLine> {
+        let buffer = self.buffer.lock().unwrap();
+        let mut lines = buffer
+            .lines
+            .iter()
+            .rev()
+            .filter(|line| filter.matches(line))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect::<Vec<_>>();
+        lines.reverse();
+        lines
+    }
+}
+
+#[test]
+fn test_levels_and_filter() {
+    let logs = ServerLogs::new(3);
+    logs.push(
+        LogStream::Stderr,
+        "2024-01-01 12:00:00.000 | DEBUG    | omuserver:main:1 - Loading".to_string(),
+    );
+    logs.push(
+        LogStream::Stderr,
+        "2024-01-01 12:00:00.000 | ERROR    | omuserver:main:2 - Failed".to_string(),
+    );
+    logs.push(
+        LogStream::Stderr,
+        "Traceback (most recent call last):".to_string(),
+    );
+    logs.push(LogStream::Stdout, "plain output".to_string());
+
+    let lines = logs.query(&LogFilter::default());
+    assert_eq!(
+        lines.iter().map(|line| line.seq).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(lines[1].level, LogLevel::Error);
+    assert_eq!(lines[2].level, LogLevel::Info);
+
+    let errors = logs.query(&LogFilter {
+        min_level: Some(LogLevel::Warning),
+        limit: Some(1),
+        ..Default::default()
+    });
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, "Traceback (most recent call last):");
+}
@@ -1,10 +1,21 @@
-use std::{fs::create_dir_all, path::PathBuf};
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{ensure, Result};
+use directories::ProjectDirs;
 use log::{info, warn};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
-use crate::{server::ServerConfig, sources::py::PythonVersionRequest, APP_DIRECTORY};
+use crate::{server::ServerConfig, sources::py::PythonVersionRequest};
+
+static APP_DIRECTORY: Lazy<ProjectDirs> =
+    Lazy::new(|| match ProjectDirs::from("com", "OMUAPPS", "Dashboard") {
+        Some(proj_dirs) => proj_dirs,
+        None => panic!("Failed to get project directories!"),
+    });
 
 static PYTHON_VERSION: PythonVersionRequest = PythonVersionRequest {
     name: None,
@@ -57,15 +68,24 @@ impl AppOptions {
         Ok(options)
     }
 
+    pub fn get_uv_cache_dir(&self) -> PathBuf {
+        self.appdir.join("uv-cache")
+    }
+
     pub fn get_log_dir(&self) -> PathBuf {
-        // <workdir>/logs/<year>-<month>-<day>
-        let now = chrono::Local::now();
-        self.workdir
-            .join("logs")
-            .join(now.format("%Y-%m-%d").to_string())
+        log_dir(&self.workdir)
     }
 }
 
+/// Today's log directory under `workdir`.
+pub fn log_dir(workdir: &Path) -> PathBuf {
+    // <workdir>/logs/<year>-<month>-<day>
+    let now = chrono::Local::now();
+    workdir
+        .join("logs")
+        .join(now.format("%Y-%m-%d").to_string())
+}
+
 fn get_data_dir() -> std::path::PathBuf {
     return APP_DIRECTORY.data_dir().to_path_buf();
 }
@@ -73,11 +93,47 @@ fn get_data_dir() -> std::path::PathBuf {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppConfig {
     pub enable_beta: bool,
+    /// Lets uv use its global cache instead of the one under the app directory,
+    /// for developers who share it with other projects.
+    #[serde(default)]
+    pub share_uv_cache: bool,
+    #[serde(default)]
+    pub server_lifecycle: ServerLifecycle,
+}
+
+/// What happens to the server when the dashboard exits.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerLifecycle {
+    /// The server keeps serving overlays, the next session attaches to it.
+    KeepRunning,
+    StopOnExit,
+    /// Like `StopOnExit`, and on Linux the server is also signalled when the
+    /// dashboard crashes.
+    DieWithParent,
+}
+
+impl Default for ServerLifecycle {
+    fn default() -> Self {
+        ServerLifecycle::KeepRunning
+    }
 }
 
 impl AppConfig {
+    pub fn path(options: &AppOptions) -> PathBuf {
+        options.appdir.join("config.json")
+    }
+
+    /// The cache directory uv should use, `None` to use its global cache.
+    pub fn uv_cache_dir(&self, options: &AppOptions) -> Option<PathBuf> {
+        if self.share_uv_cache {
+            None
+        } else {
+            Some(options.get_uv_cache_dir())
+        }
+    }
+
     pub fn ensure(options: &AppOptions) -> Self {
-        let path = &options.appdir.join("config.json");
+        let path = &Self::path(options);
         if path.exists() {
             info!("Loading config from {}", path.display());
             match Self::load(path) {
@@ -92,7 +148,11 @@ impl AppConfig {
                 path.display()
             );
         }
-        let config = AppConfig { enable_beta: false };
+        let config = AppConfig {
+            enable_beta: false,
+            share_uv_cache: false,
+            server_lifecycle: ServerLifecycle::default(),
+        };
         config.store(path).unwrap_or_else(|err| {
             warn!("Failed to store default config: {}", err);
         });
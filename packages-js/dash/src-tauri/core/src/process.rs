@@ -0,0 +1,853 @@
+use std::{
+    io::Read,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::Arc,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const ATTACHED_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum ProcessError {
+    Io(std::io::Error),
+    TimedOut { timeout: Duration },
+}
+
+impl std::fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessError::Io(err) => write!(f, "{}", err),
+            ProcessError::TimedOut { timeout } => {
+                write!(f, "timed out after {}s", timeout.as_secs())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// Asks the processes to exit.
+    ///
+    /// Same as [`Signal::Kill`] on Windows: `taskkill` without `/F` only
+    /// posts WM_CLOSE, which a console process without a window never sees.
+    Terminate,
+    Kill,
+}
+
+/// Runs and starts the processes of `Python` and `Uv`, so lifecycle code can be
+/// tested against a fake instead of real binaries.
+pub trait ProcessRunner: Send + Sync {
+    /// Runs the command to completion, see [`run`].
+    fn run(&self, cmd: &mut Command, timeout: Duration) -> Result<Output, ProcessError>;
+    /// Starts a long-running process with piped output.
+    fn spawn(&self, cmd: &mut Command) -> std::io::Result<Box<dyn ChildProcess>>;
+    /// Signals a process started by `spawn` and everything it spawned.
+    fn signal(&self, pid: u32, signal: Signal) -> std::io::Result<()>;
+    /// Looks up a running process, see [`inspect_process`].
+    fn inspect(&self, pid: u32) -> Option<ProcessInfo>;
+    fn stats(&self, pid: u32) -> Option<ProcessStats>;
+}
+
+/// What identifies a running process beyond its pid, which the system reuses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub executable: Option<PathBuf>,
+    /// When the process started, in a platform specific format.
+    pub start_time: String,
+}
+
+/// How a process ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Status(ExitStatus),
+    /// The process is gone, but only its parent could tell how it ended.
+    Unknown,
+}
+
+impl Exit {
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Exit::Status(status) => status.code(),
+            Exit::Unknown => None,
+        }
+    }
+}
+
+pub trait ChildProcess: Send {
+    fn id(&self) -> u32;
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>>;
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>>;
+    fn wait(&mut self) -> std::io::Result<Exit>;
+}
+
+impl ChildProcess for Child {
+    fn id(&self) -> u32 {
+        Child::id(self)
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stdout
+            .take()
+            .map(|stdout| Box::new(stdout) as Box<dyn Read + Send>)
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stderr
+            .take()
+            .map(|stderr| Box::new(stderr) as Box<dyn Read + Send>)
+    }
+
+    fn wait(&mut self) -> std::io::Result<Exit> {
+        Child::wait(self).map(Exit::Status)
+    }
+}
+
+/// Runs real processes.
+pub struct SystemRunner;
+
+impl ProcessRunner for SystemRunner {
+    fn run(&self, cmd: &mut Command, timeout: Duration) -> Result<Output, ProcessError> {
+        run(cmd, timeout)
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> std::io::Result<Box<dyn ChildProcess>> {
+        new_process_group(cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map(|child| Box::new(child) as Box<dyn ChildProcess>)
+    }
+
+    fn signal(&self, pid: u32, signal: Signal) -> std::io::Result<()> {
+        signal_process_tree(pid, signal)
+    }
+
+    fn inspect(&self, pid: u32) -> Option<ProcessInfo> {
+        inspect_process(pid)
+    }
+
+    fn stats(&self, pid: u32) -> Option<ProcessStats> {
+        process_stats(pid)
+    }
+}
+
+pub fn system_runner() -> Arc<dyn ProcessRunner> {
+    Arc::new(SystemRunner)
+}
+
+/// Starts the command in a process group of its own, so that
+/// [`kill_process_tree`] also reaches the processes it spawns.
+pub fn new_process_group(cmd: &mut Command) -> &mut Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setpgid is async-signal-safe.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setpgid(0, 0) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    cmd
+}
+
+/// Has Linux send SIGTERM to the process once its parent exits.
+///
+/// The signal is tied to the thread that spawns the process, so callers must
+/// spawn from a thread that outlives it.
+#[cfg(target_os = "linux")]
+pub fn die_with_parent(cmd: &mut Command) -> &mut Command {
+    use std::os::unix::process::CommandExt;
+    let parent = std::process::id() as libc::pid_t;
+    // SAFETY: prctl and getppid are async-signal-safe, and the error is
+    // built without allocating.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // The parent may have exited before the signal was set up.
+            if libc::getppid() != parent {
+                return Err(std::io::Error::from_raw_os_error(libc::ESRCH));
+            }
+            Ok(())
+        });
+    }
+    cmd
+}
+
+/// Only Linux can tie a process to its parent, elsewhere this does nothing.
+#[cfg(not(target_os = "linux"))]
+pub fn die_with_parent(cmd: &mut Command) -> &mut Command {
+    cmd
+}
+
+/// Signals a process started with [`new_process_group`] and everything it spawned.
+pub fn signal_process_tree(pid: u32, signal: Signal) -> std::io::Result<()> {
+    if pid == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "refusing to signal pid 0",
+        ));
+    }
+    #[cfg(unix)]
+    {
+        let signal = match signal {
+            Signal::Terminate => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        };
+        // The process leads its own process group, see `new_process_group`.
+        // SAFETY: killpg only sends a signal.
+        if unsafe { libc::killpg(pid as libc::pid_t, signal) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // See `Signal::Terminate` for why both signals force the exit.
+        let _ = signal;
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/F", "/T", "/PID", &pid.to_string()]);
+        let status = cmd
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .creation_flags(0x08000000)
+            .status()?;
+        if !status.success() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("taskkill exited with {}", status),
+            ));
+        }
+        return Ok(());
+    }
+    #[allow(unreachable_code)]
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Looks up a running process, `None` if there is none with the pid.
+#[cfg(target_os = "linux")]
+pub fn inspect_process(pid: u32) -> Option<ProcessInfo> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The fields follow the command name, which is in parentheses and may
+    // contain spaces. `starttime` is the 22nd field, `state` the 3rd.
+    let fields = stat
+        .rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    if fields.first() == Some(&"Z") {
+        return None;
+    }
+    Some(ProcessInfo {
+        executable: std::fs::read_link(format!("/proc/{}/exe", pid)).ok(),
+        start_time: fields.get(19)?.to_string(),
+    })
+}
+
+/// Resource usage of a process at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessStats {
+    /// CPU time spent in user and kernel mode since the process started.
+    pub cpu_time: Duration,
+    pub rss_bytes: u64,
+    pub threads: Option<u32>,
+    /// Open file descriptors, or handles on Windows.
+    pub open_files: Option<u32>,
+}
+
+/// Reads the resource usage of a running process.
+#[cfg(target_os = "linux")]
+pub fn process_stats(pid: u32) -> Option<ProcessStats> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let fields = stat
+        .rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let field = |index: usize| fields.get(index)?.parse::<u64>().ok();
+    // SAFETY: sysconf only reads configuration values.
+    let (ticks, page_size) = unsafe {
+        (
+            libc::sysconf(libc::_SC_CLK_TCK),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    // `utime` and `stime` are the 14th and 15th fields, `num_threads` the
+    // 20th and `rss` the 24th, counting from the pid.
+    let cpu_ticks = field(11)? + field(12)?;
+    Some(ProcessStats {
+        cpu_time: Duration::from_millis(cpu_ticks * 1000 / ticks.max(1) as u64),
+        rss_bytes: field(21)? * page_size.max(0) as u64,
+        threads: field(17).map(|threads| threads as u32),
+        open_files: std::fs::read_dir(format!("/proc/{}/fd", pid))
+            .ok()
+            .map(|entries| entries.count() as u32),
+    })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn ps(pid: u32, field: &str) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", field])
+        .output()
+        .ok()?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then(|| value)
+}
+
+/// Parses the `[[dd-]hh:]mm:ss[.cc]` CPU time printed by `ps`.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+fn parse_cpu_time(time: &str) -> Option<Duration> {
+    let (days, time) = match time.split_once('-') {
+        Some((days, time)) => (days.parse::<f64>().ok()?, time),
+        None => (0.0, time),
+    };
+    let seconds = time.split(':').try_fold(0.0, |total, part| {
+        Some(total * 60.0 + part.parse::<f64>().ok()?)
+    })?;
+    Some(Duration::from_secs_f64(days * 86400.0 + seconds))
+}
+
+/// Looks up a running process, `None` if there is none with the pid.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn inspect_process(pid: u32) -> Option<ProcessInfo> {
+    Some(ProcessInfo {
+        executable: ps(pid, "comm=").map(PathBuf::from),
+        start_time: ps(pid, "lstart=")?,
+    })
+}
+
+/// Reads the resource usage of a running process.
+#[cfg(target_os = "macos")]
+pub fn process_stats(pid: u32) -> Option<ProcessStats> {
+    // `mach_timebase_info` is deprecated in libc in favour of another crate.
+    #[repr(C)]
+    struct TimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
+    extern "C" {
+        fn mach_timebase_info(info: *mut TimebaseInfo) -> libc::c_int;
+    }
+
+    let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+    // SAFETY: both calls only write to the buffers passed, which are zeroed
+    // plain data of the size given.
+    let (info, timebase) = unsafe {
+        let mut info = std::mem::zeroed::<libc::proc_taskinfo>();
+        let read = libc::proc_pidinfo(
+            pid as libc::c_int,
+            libc::PROC_PIDTASKINFO,
+            0,
+            &mut info as *mut libc::proc_taskinfo as *mut libc::c_void,
+            size,
+        );
+        if read != size {
+            return None;
+        }
+        let mut timebase = TimebaseInfo { numer: 0, denom: 0 };
+        if mach_timebase_info(&mut timebase) != 0 || timebase.denom == 0 {
+            return None;
+        }
+        (info, timebase)
+    };
+    // The CPU times are in Mach absolute time units, which are only
+    // nanoseconds on Intel.
+    let ticks = (info.pti_total_user + info.pti_total_system) as u128;
+    let nanos = ticks * timebase.numer as u128 / timebase.denom as u128;
+    Some(ProcessStats {
+        cpu_time: Duration::from_nanos(nanos as u64),
+        rss_bytes: info.pti_resident_size,
+        threads: Some(info.pti_threadnum as u32),
+        open_files: None,
+    })
+}
+
+/// Reads the resource usage of a running process.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+pub fn process_stats(pid: u32) -> Option<ProcessStats> {
+    Some(ProcessStats {
+        cpu_time: parse_cpu_time(&ps(pid, "time=")?)?,
+        rss_bytes: ps(pid, "rss=")?.parse::<u64>().ok()? * 1024,
+        threads: None,
+        open_files: None,
+    })
+}
+
+/// The parts of the Win32 API used to inspect processes.
+#[cfg(target_os = "windows")]
+mod win32 {
+    pub type Handle = isize;
+
+    pub const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    pub const STILL_ACTIVE: u32 = 259;
+    pub const TH32CS_SNAPPROCESS: u32 = 0x2;
+    pub const INVALID_HANDLE_VALUE: Handle = -1;
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct FileTime {
+        pub low: u32,
+        pub high: u32,
+    }
+
+    impl FileTime {
+        /// The time in 100 nanosecond intervals.
+        pub fn ticks(&self) -> u64 {
+            (self.high as u64) << 32 | self.low as u64
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct ProcessMemoryCounters {
+        pub cb: u32,
+        pub page_fault_count: u32,
+        pub peak_working_set_size: usize,
+        pub working_set_size: usize,
+        pub quota_peak_paged_pool_usage: usize,
+        pub quota_paged_pool_usage: usize,
+        pub quota_peak_non_paged_pool_usage: usize,
+        pub quota_non_paged_pool_usage: usize,
+        pub pagefile_usage: usize,
+        pub peak_pagefile_usage: usize,
+    }
+
+    #[repr(C)]
+    pub struct ProcessEntry32W {
+        pub size: u32,
+        pub usage: u32,
+        pub process_id: u32,
+        pub default_heap_id: usize,
+        pub module_id: u32,
+        pub threads: u32,
+        pub parent_process_id: u32,
+        pub pri_class_base: i32,
+        pub flags: u32,
+        pub exe_file: [u16; 260],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn OpenProcess(access: u32, inherit: i32, pid: u32) -> Handle;
+        pub fn CloseHandle(handle: Handle) -> i32;
+        pub fn GetExitCodeProcess(handle: Handle, code: *mut u32) -> i32;
+        pub fn GetProcessTimes(
+            handle: Handle,
+            creation: *mut FileTime,
+            exit: *mut FileTime,
+            kernel: *mut FileTime,
+            user: *mut FileTime,
+        ) -> i32;
+        pub fn K32GetProcessMemoryInfo(
+            handle: Handle,
+            counters: *mut ProcessMemoryCounters,
+            size: u32,
+        ) -> i32;
+        pub fn GetProcessHandleCount(handle: Handle, count: *mut u32) -> i32;
+        pub fn QueryFullProcessImageNameW(
+            handle: Handle,
+            flags: u32,
+            name: *mut u16,
+            size: *mut u32,
+        ) -> i32;
+        pub fn CreateToolhelp32Snapshot(flags: u32, pid: u32) -> Handle;
+        pub fn Process32FirstW(snapshot: Handle, entry: *mut ProcessEntry32W) -> i32;
+        pub fn Process32NextW(snapshot: Handle, entry: *mut ProcessEntry32W) -> i32;
+    }
+
+    /// Closes the handle when dropped.
+    pub struct OwnedHandle(pub Handle);
+
+    impl Drop for OwnedHandle {
+        fn drop(&mut self) {
+            // SAFETY: the handle was opened by us and is closed only here.
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+
+    /// Opens a process that is still running for querying.
+    pub fn open_process(pid: u32) -> Option<OwnedHandle> {
+        // SAFETY: OpenProcess takes no pointers, and a handle it returns is
+        // owned by the caller.
+        let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if handle == 0 {
+            return None;
+        }
+        let handle = OwnedHandle(handle);
+        let mut code = 0u32;
+        // SAFETY: `code` outlives the call.
+        if unsafe { GetExitCodeProcess(handle.0, &mut code) } == 0 || code != STILL_ACTIVE {
+            return None;
+        }
+        Some(handle)
+    }
+
+    /// Returns the creation time and the CPU time spent in kernel and user mode.
+    pub fn process_times(handle: &OwnedHandle) -> Option<(u64, u64)> {
+        let mut creation = FileTime::default();
+        let mut exit = FileTime::default();
+        let mut kernel = FileTime::default();
+        let mut user = FileTime::default();
+        // SAFETY: the times outlive the call.
+        let ok =
+            unsafe { GetProcessTimes(handle.0, &mut creation, &mut exit, &mut kernel, &mut user) };
+        (ok != 0).then(|| (creation.ticks(), kernel.ticks() + user.ticks()))
+    }
+
+    /// Counts the threads of the process from a snapshot of all processes.
+    pub fn thread_count(pid: u32) -> Option<u32> {
+        // SAFETY: the snapshot is closed by `OwnedHandle`, and the entry
+        // outlives the calls that fill it.
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let snapshot = OwnedHandle(snapshot);
+            let mut entry = std::mem::zeroed::<ProcessEntry32W>();
+            entry.size = std::mem::size_of::<ProcessEntry32W>() as u32;
+            let mut found = Process32FirstW(snapshot.0, &mut entry) != 0;
+            while found {
+                if entry.process_id == pid {
+                    return Some(entry.threads);
+                }
+                found = Process32NextW(snapshot.0, &mut entry) != 0;
+            }
+            None
+        }
+    }
+}
+
+/// Looks up a running process, `None` if there is none with the pid.
+#[cfg(target_os = "windows")]
+pub fn inspect_process(pid: u32) -> Option<ProcessInfo> {
+    use std::os::windows::ffi::OsStringExt;
+    let handle = win32::open_process(pid)?;
+    let (creation, _) = win32::process_times(&handle)?;
+    let mut name = vec![0u16; 32768];
+    let mut size = name.len() as u32;
+    // SAFETY: `size` is the length of `name`, which outlives the call.
+    let executable = unsafe {
+        win32::QueryFullProcessImageNameW(handle.0, 0, name.as_mut_ptr(), &mut size) != 0
+    }
+    .then(|| PathBuf::from(std::ffi::OsString::from_wide(&name[..size as usize])));
+    Some(ProcessInfo {
+        executable,
+        // The same FILETIME as `Process.StartTime.ToFileTimeUtc()`, which
+        // earlier versions recorded.
+        start_time: creation.to_string(),
+    })
+}
+
+/// Reads the resource usage of a running process.
+#[cfg(target_os = "windows")]
+pub fn process_stats(pid: u32) -> Option<ProcessStats> {
+    let handle = win32::open_process(pid)?;
+    let (_, cpu_ticks) = win32::process_times(&handle)?;
+    let mut counters = win32::ProcessMemoryCounters {
+        cb: std::mem::size_of::<win32::ProcessMemoryCounters>() as u32,
+        ..Default::default()
+    };
+    let size = counters.cb;
+    let mut handles = 0u32;
+    // SAFETY: `size` is the size of `counters`, and both outlive the calls.
+    let (memory, handles) = unsafe {
+        let memory = win32::K32GetProcessMemoryInfo(handle.0, &mut counters, size) != 0;
+        let counted = win32::GetProcessHandleCount(handle.0, &mut handles) != 0;
+        (memory, if counted { Some(handles) } else { None })
+    };
+    if !memory {
+        return None;
+    }
+    Some(ProcessStats {
+        cpu_time: Duration::from_nanos(cpu_ticks * 100),
+        rss_bytes: counters.working_set_size as u64,
+        threads: win32::thread_count(pid),
+        open_files: handles,
+    })
+}
+
+/// A process started by an earlier session, which can only be watched from
+/// the outside.
+pub struct AttachedProcess {
+    pid: u32,
+    start_time: String,
+    runner: Arc<dyn ProcessRunner>,
+}
+
+impl AttachedProcess {
+    pub fn new(pid: u32, start_time: String, runner: Arc<dyn ProcessRunner>) -> Self {
+        Self {
+            pid,
+            start_time,
+            runner,
+        }
+    }
+}
+
+impl ChildProcess for AttachedProcess {
+    fn id(&self) -> u32 {
+        self.pid
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        None
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        None
+    }
+
+    /// Polls until the process is gone. Its exit status is only known to its
+    /// parent, so it always ends as [`Exit::Unknown`].
+    fn wait(&mut self) -> std::io::Result<Exit> {
+        while let Some(info) = self.runner.inspect(self.pid) {
+            if info.start_time != self.start_time {
+                break;
+            }
+            std::thread::sleep(ATTACHED_POLL_INTERVAL);
+        }
+        Ok(Exit::Unknown)
+    }
+}
+
+/// Kills the child and everything it spawned.
+pub fn kill_process_tree(child: &mut Child) {
+    if signal_process_tree(child.id(), Signal::Kill).is_ok() {
+        return;
+    }
+    if let Err(err) = child.kill() {
+        warn!("Failed to kill process {}: {}", child.id(), err);
+    }
+}
+
+fn read_to_end(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Like [`Command::output`], but kills the whole process tree once `timeout` expires.
+pub fn run(cmd: &mut Command, timeout: Duration) -> Result<Output, ProcessError> {
+    let mut child = new_process_group(cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(ProcessError::Io)?;
+    // Drain the pipes while waiting, a full pipe would block the child forever.
+    let stdout = read_to_end(child.stdout.take());
+    let stderr = read_to_end(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                kill_process_tree(&mut child);
+                let _ = child.wait();
+                return Err(ProcessError::TimedOut { timeout });
+            }
+            Err(err) => {
+                kill_process_tree(&mut child);
+                return Err(ProcessError::Io(err));
+            }
+        }
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::{
+        collections::{HashMap, VecDeque},
+        io::Read,
+        process::{Command, ExitStatus, Output},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{
+        ChildProcess, Exit, ProcessError, ProcessInfo, ProcessRunner, ProcessStats, Signal,
+    };
+
+    #[derive(Debug, Clone, Default)]
+    pub struct FakeResult {
+        pub code: i32,
+        pub stdout: String,
+        pub stderr: String,
+    }
+
+    impl FakeResult {
+        pub fn exit(code: i32) -> Self {
+            Self {
+                code,
+                ..Default::default()
+            }
+        }
+
+        pub fn stdout(stdout: &str) -> Self {
+            Self {
+                stdout: stdout.to_string(),
+                ..Default::default()
+            }
+        }
+
+        pub fn stderr(code: i32, stderr: &str) -> Self {
+            Self {
+                code,
+                stderr: stderr.to_string(),
+                ..Default::default()
+            }
+        }
+    }
+
+    /// Records every command and answers with scripted results.
+    ///
+    /// Commands without a matching script succeed with empty output.
+    #[derive(Default)]
+    pub struct FakeRunner {
+        scripts: Mutex<VecDeque<(Vec<String>, FakeResult)>>,
+        calls: Mutex<Vec<String>>,
+        processes: Mutex<HashMap<u32, ProcessInfo>>,
+    }
+
+    impl FakeRunner {
+        pub fn new() -> Arc<Self> {
+            Arc::new(Self::default())
+        }
+
+        /// Answers the next command whose arguments include all of `args`.
+        pub fn script(&self, args: &[&str], result: FakeResult) {
+            let args = args.iter().map(|arg| arg.to_string()).collect();
+            self.scripts.lock().unwrap().push_back((args, result));
+        }
+
+        /// The arguments of every command so far, joined by spaces.
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// Pretends a process is running until it is signalled.
+        pub fn add_process(&self, pid: u32, info: ProcessInfo) {
+            self.processes.lock().unwrap().insert(pid, info);
+        }
+
+        /// Pretends a process added by `add_process` died on its own.
+        pub fn remove_process(&self, pid: u32) {
+            self.processes.lock().unwrap().remove(&pid);
+        }
+
+        pub fn called(&self, args: &str) -> bool {
+            self.calls().iter().any(|call| call.contains(args))
+        }
+
+        fn answer(&self, cmd: &Command) -> FakeResult {
+            let args = cmd
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            self.calls.lock().unwrap().push(args.join(" "));
+            let mut scripts = self.scripts.lock().unwrap();
+            let position = scripts
+                .iter()
+                .position(|(pattern, _)| pattern.iter().all(|arg| args.contains(arg)));
+            position
+                .and_then(|position| scripts.remove(position))
+                .map(|(_, result)| result)
+                .unwrap_or_default()
+        }
+    }
+
+    fn exit_status(code: i32) -> ExitStatus {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            ExitStatus::from_raw(code << 8)
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::ExitStatusExt;
+            ExitStatus::from_raw(code as u32)
+        }
+    }
+
+    impl ProcessRunner for FakeRunner {
+        fn run(&self, cmd: &mut Command, _timeout: Duration) -> Result<Output, ProcessError> {
+            let result = self.answer(cmd);
+            Ok(Output {
+                status: exit_status(result.code),
+                stdout: result.stdout.into_bytes(),
+                stderr: result.stderr.into_bytes(),
+            })
+        }
+
+        fn spawn(&self, cmd: &mut Command) -> std::io::Result<Box<dyn ChildProcess>> {
+            Ok(Box::new(FakeChild(Some(self.answer(cmd)))))
+        }
+
+        fn signal(&self, pid: u32, signal: Signal) -> std::io::Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("signal {:?} {}", signal, pid));
+            self.processes.lock().unwrap().remove(&pid);
+            Ok(())
+        }
+
+        fn inspect(&self, pid: u32) -> Option<ProcessInfo> {
+            self.processes.lock().unwrap().get(&pid).cloned()
+        }
+
+        fn stats(&self, _pid: u32) -> Option<ProcessStats> {
+            None
+        }
+    }
+
+    /// A process that exits as soon as it is waited on.
+    struct FakeChild(Option<FakeResult>);
+
+    impl ChildProcess for FakeChild {
+        fn id(&self) -> u32 {
+            0
+        }
+
+        fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+            let stdout = self.0.as_ref()?.stdout.clone().into_bytes();
+            Some(Box::new(std::io::Cursor::new(stdout)))
+        }
+
+        fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+            let stderr = self.0.as_ref()?.stderr.clone().into_bytes();
+            Some(Box::new(std::io::Cursor::new(stderr)))
+        }
+
+        fn wait(&mut self) -> std::io::Result<Exit> {
+            let code = self.0.take().map_or(0, |result| result.code);
+            Ok(Exit::Status(exit_status(code)))
+        }
+    }
+}
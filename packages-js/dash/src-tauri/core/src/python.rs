@@ -1,16 +1,28 @@
-use std::{path::PathBuf, process::Command};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::Arc,
+    time::Duration,
+};
 
 use crate::{
+    environment::ProcessEnv,
     options::AppOptions,
+    process::{
+        system_runner, AttachedProcess, ChildProcess, ProcessError, ProcessInfo, ProcessRunner,
+        ProcessStats, Signal,
+    },
     sources::py::{get_download_url, PythonVersion},
     sync::{read_venv_marker, write_venv_marker},
     utils::{archive::unpack_archive, checksum::check_checksum, download::download_url},
 };
 
+#[derive(Clone)]
 pub struct Python {
     pub version: PythonVersion,
     pub path: PathBuf,
     pub python_bin: PathBuf,
+    runner: Arc<dyn ProcessRunner>,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -52,6 +64,7 @@ impl Python {
                 version,
                 path: python_path,
                 python_bin,
+                runner: system_runner(),
             });
         };
         match read_venv_marker(&python_path) {
@@ -59,11 +72,13 @@ impl Python {
                 version: version.python,
                 path: python_path,
                 python_bin: python_bin,
+                runner: system_runner(),
             }),
             None => Self::download(&options, &on_progress).map(|version| Self {
                 version,
                 path: python_path,
                 python_bin,
+                runner: system_runner(),
             }),
         }
     }
@@ -132,8 +147,62 @@ impl Python {
         Ok(version)
     }
 
+    /// Returns the interpreter of a virtual environment created from this Python at `path`.
+    pub fn venv(&self, path: &Path) -> Self {
+        let python_bin = if cfg!(target_os = "windows") {
+            path.join("Scripts").join("python.exe")
+        } else {
+            path.join("bin").join("python")
+        };
+        Self {
+            version: self.version.clone(),
+            path: path.to_path_buf(),
+            python_bin,
+            runner: self.runner.clone(),
+        }
+    }
+
+    /// Returns this Python with its processes run by `runner` instead.
+    #[cfg(test)]
+    pub fn with_runner(self, runner: Arc<dyn ProcessRunner>) -> Self {
+        Self { runner, ..self }
+    }
+
+    /// Runs a command built by [`Python::cmd`] to completion.
+    pub fn run(&self, cmd: &mut Command, timeout: Duration) -> Result<Output, ProcessError> {
+        self.runner.run(cmd, timeout)
+    }
+
+    /// Starts a long-running command built by [`Python::cmd`].
+    pub fn spawn(&self, cmd: &mut Command) -> std::io::Result<Box<dyn ChildProcess>> {
+        self.runner.spawn(cmd)
+    }
+
+    pub fn signal(&self, pid: u32, signal: Signal) -> std::io::Result<()> {
+        self.runner.signal(pid, signal)
+    }
+
+    pub fn inspect(&self, pid: u32) -> Option<ProcessInfo> {
+        self.runner.inspect(pid)
+    }
+
+    pub fn stats(&self, pid: u32) -> Option<ProcessStats> {
+        self.runner.stats(pid)
+    }
+
+    /// Watches a process that was started by an earlier session.
+    pub fn attach(&self, pid: u32, start_time: String) -> Box<dyn ChildProcess> {
+        Box::new(AttachedProcess::new(pid, start_time, self.runner.clone()))
+    }
+
     pub fn cmd(&self) -> Command {
+        self.cmd_with(&ProcessEnv::default())
+    }
+
+    /// Builds a command for the interpreter running in `env`.
+    pub fn cmd_with(&self, env: &ProcessEnv) -> Command {
         let mut command = Command::new(&self.python_bin);
+        env.apply(&mut command);
 
         #[cfg(target_os = "windows")]
         {
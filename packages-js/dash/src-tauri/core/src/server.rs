@@ -0,0 +1,2464 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::canonicalize;
+use std::io::{BufRead, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::{info, log, warn};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use crate::environment::{ProcessEnv, ResourceLimits};
+use crate::events::{EventSink, ServerEvent};
+use crate::index::{IndexConfig, PackageIndex, REDACTED};
+use crate::logs::{LogFilter, LogStream, ServerLogs};
+use crate::options::{log_dir, AppConfig, AppOptions, ServerLifecycle};
+use crate::process::{die_with_parent, ChildProcess, Exit, ProcessError, Signal};
+use crate::sync::{
+    read_environment_stamp, remove_environment_stamp, write_environment_stamp, EnvironmentStamp,
+    ENVIRONMENTS_DIR,
+};
+use crate::utils::{
+    checksum::compute_checksum, distribution::distribution_requirement, filesystem::remove_dir_all,
+};
+use crate::uv::{normalize_package_name, requirement_name, UvEnsureError, UvEnsureProgress};
+use crate::version::VERSION;
+use crate::{
+    python::{Python, PythonEnsureError, PythonEnsureProgress},
+    uv::Uv,
+};
+
+/// `omuserver --stop` can hang on a process it fails to inspect.
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the server may take to shut down after being asked to.
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(10);
+const UNINSTALL_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+const LATEST_PIP: &str = "pip==23.3.2";
+static REQUIREMENTS: Lazy<String> = Lazy::new(|| {
+    format!(
+        "
+omuserver=={VERSION}
+omuplugin_obs=={VERSION}
+omu_chat=={VERSION}
+omu_chat_youtube=={VERSION}
+omu_chat_twitch=={VERSION}
+omu_chatprovider=={VERSION}"
+    )
+});
+static DEPRECATED_REQUIREMENTS: Lazy<String> = Lazy::new(|| {
+    format!(
+        "
+omuplugin_discordrpc=={VERSION}
+    "
+    )
+});
+
+/// Packages the dashboard relies on besides [`REQUIREMENTS`].
+const CORE_PACKAGES: &[&str] = &["pip", "uv", "omu"];
+
+/// Lines of server output included in crash reports.
+const CRASH_REPORT_LINES: usize = 200;
+const RESTART_CODE: i32 = 100;
+const DEFAULT_PORT: u16 = 26423;
+const ACTIVE_ENVIRONMENT_FILE: &str = "active";
+const PID_FILE: &str = "server.pid";
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long a program holding the port may take to answer `/version` before
+/// it is treated as foreign and another port is used.
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Long enough for the readiness check to finish, including its last probe.
+pub const READY_WAIT_TIMEOUT: Duration = Duration::from_secs(35);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub workdir: PathBuf,
+    pub port: u16,
+    pub hash: String,
+    #[serde(default)]
+    pub index: IndexConfig,
+    /// Requirements of plugins added by the user, installed along with the server.
+    #[serde(default)]
+    pub plugins: Vec<String>,
+    #[serde(default)]
+    pub sideloaded: Vec<SideloadedPlugin>,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    #[serde(default)]
+    pub port_range: PortRange,
+    #[serde(default)]
+    pub monitor: MonitorConfig,
+    #[serde(default)]
+    pub launch: LaunchOptions,
+    /// Applied to the server process only, not to installs.
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// Where the token is written instead of [`ServerConfig::get_token_path`]'s default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_path: Option<PathBuf>,
+}
+
+/// Ports to move to when the configured one is taken by another program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Default for PortRange {
+    fn default() -> Self {
+        Self {
+            start: DEFAULT_PORT,
+            end: DEFAULT_PORT + 100,
+        }
+    }
+}
+
+impl PortRange {
+    pub fn find_free(&self, skip: u16) -> Option<u16> {
+        (self.start..=self.end)
+            .filter(|port| *port != skip)
+            .find(|port| is_port_free(*port))
+    }
+}
+
+/// Options of omuserver the dashboard passes through on the next start or restart.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchOptions {
+    /// Runs omuserver with `--debug`, which enables tracemalloc.
+    pub debug: bool,
+    /// Appended after the arguments set by the dashboard, e.g. `--trusted-host`.
+    pub extra_args: Vec<String>,
+    pub extra_env: BTreeMap<String, String>,
+}
+
+/// Arguments the dashboard sets itself or that make omuserver do something
+/// other than serve.
+const RESERVED_ARGS: &[&str] = &[
+    "--debug",
+    "--stop",
+    "--uninstall",
+    "--token",
+    "--token-file",
+    "--dashboard-path",
+    "--port",
+    "--hash",
+    "--index-url",
+];
+const RESERVED_ENV: &[&str] = &[
+    "UV_INDEX_URL",
+    "UV_EXTRA_INDEX_URL",
+    "UV_INSECURE_HOST",
+    "UV_CACHE_DIR",
+    "NETRC",
+];
+
+impl LaunchOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        for arg in &self.extra_args {
+            if arg.is_empty() || arg.contains('\0') {
+                return Err(format!("Invalid argument {:?}", arg));
+            }
+            let name = arg.split('=').next().unwrap_or_default();
+            if RESERVED_ARGS.contains(&name) {
+                return Err(format!("Argument {} is set by the dashboard", name));
+            }
+        }
+        for (key, value) in &self.extra_env {
+            if key.is_empty() || key.contains(['=', '\0']) || value.contains('\0') {
+                return Err(format!("Invalid environment variable {:?}", key));
+            }
+            if RESERVED_ENV
+                .iter()
+                .any(|reserved| reserved.eq_ignore_ascii_case(key))
+            {
+                return Err(format!(
+                    "Environment variable {} is set by the dashboard",
+                    key
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Summary for logs and bug reports, leaving out the values of `extra_env`,
+    /// which often hold tokens.
+    pub fn describe(&self) -> String {
+        let env = self.extra_env.keys().collect::<Vec<_>>();
+        format!(
+            "debug: {}, extra args: {:?}, extra env: {:?}",
+            self.debug, self.extra_args, env
+        )
+    }
+
+    /// Replaces the values of `extra_env` in `text`, e.g. the `Debug` output of
+    /// a command, which lists its environment.
+    pub fn redact(&self, text: &str) -> String {
+        self.extra_env
+            .values()
+            .filter(|value| !value.is_empty())
+            .fold(text.to_string(), |text, value| {
+                let escaped = format!("{:?}", value);
+                text.replace(&escaped[1..escaped.len() - 1], REDACTED)
+                    .replace(value.as_str(), REDACTED)
+            })
+    }
+}
+
+/// How often the server's resource usage is sampled and when to warn about it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Seconds between samples, `0` turns monitoring off.
+    pub interval_secs: u64,
+    pub max_rss_mb: Option<u64>,
+    /// Share of one core, so a busy multithreaded server can exceed 100.
+    pub max_cpu_percent: Option<f64>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 10,
+            max_rss_mb: None,
+            max_cpu_percent: None,
+        }
+    }
+}
+
+/// Resource usage of the supervised server, sent as the `server_stats` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerStats {
+    pub pid: u32,
+    /// CPU usage since the previous sample, as a share of one core.
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub threads: Option<u32>,
+    pub open_files: Option<u32>,
+    /// Seconds since the process was launched or attached to.
+    pub uptime_secs: u64,
+    /// Thresholds of [`MonitorConfig`] this sample exceeds.
+    pub warnings: Vec<String>,
+}
+
+/// Identifies the server process across dashboard sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PidFile {
+    pub pid: u32,
+    /// See [`crate::process::ProcessInfo::start_time`], `None` where processes cannot be inspected.
+    pub start_time: Option<String>,
+    pub executable: Option<PathBuf>,
+    pub port: u16,
+    pub version: String,
+}
+
+impl PidFile {
+    fn path(workdir: &Path) -> PathBuf {
+        workdir.join(PID_FILE)
+    }
+
+    fn read(workdir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path(workdir)).ok()?;
+        serde_json::from_str(&content)
+            .map_err(|err| warn!("Ignoring invalid pid file: {}", err))
+            .ok()
+    }
+
+    fn write(&self, workdir: &Path) -> std::io::Result<()> {
+        std::fs::write(Self::path(workdir), serde_json::to_string_pretty(self)?)
+    }
+
+    /// Removes the pid file if it still belongs to `pid`.
+    fn remove(workdir: &Path, pid: u32) {
+        if Self::read(workdir).map_or(false, |pid_file| pid_file.pid == pid) {
+            if let Err(err) = std::fs::remove_file(Self::path(workdir)) {
+                warn!("Failed to remove pid file: {}", err);
+            }
+        }
+    }
+
+    /// Returns the start time of the process if it is still the one recorded,
+    /// rather than another process that was given the same pid.
+    fn verify(&self, python: &Python) -> Option<String> {
+        let start_time = self.start_time.as_ref()?;
+        let info = python.inspect(self.pid)?;
+        if &info.start_time != start_time {
+            return None;
+        }
+        if self.executable.is_some() && info.executable != self.executable {
+            return None;
+        }
+        Some(info.start_time)
+    }
+}
+
+/// Where clients reach the server, sent with the `server_address` event.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ServerAddress {
+    pub host: String,
+    pub port: u16,
+}
+
+/// How the supervisor restarts a server that exited unexpectedly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub enabled: bool,
+    /// Crashes tolerated within `window_secs` before giving up.
+    pub max_restarts: u32,
+    pub window_secs: u64,
+    /// Delay before the first restart, doubled for every further crash in the window.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_restarts: 5,
+            window_secs: 10 * 60,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60 * 1000,
+        }
+    }
+}
+
+impl RestartPolicy {
+    fn backoff(&self, crashes: u32) -> Duration {
+        let factor = 2u64.saturating_pow(crashes.saturating_sub(1));
+        let backoff = self.initial_backoff_ms.saturating_mul(factor);
+        Duration::from_millis(backoff.min(self.max_backoff_ms))
+    }
+}
+
+/// A plugin installed from a local wheel or source distribution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SideloadedPlugin {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+impl ServerConfig {
+    pub fn path(options: &AppOptions) -> PathBuf {
+        options.appdir.join("server.json")
+    }
+
+    pub fn ensure(options: &AppOptions) -> Self {
+        let path = &Self::path(options);
+        if path.exists() {
+            info!("Loading config from {}", path.display());
+            match Self::load(path) {
+                Ok(config) => return config,
+                Err(err) => {
+                    warn!("Failed to load config: {}, generating default config", err);
+                }
+            };
+        } else {
+            info!(
+                "Config file not found, generating default config at {}",
+                path.display()
+            );
+        }
+        let config = ServerConfig {
+            workdir: options.workdir.clone(),
+            port: DEFAULT_PORT,
+            hash: generate_hash(),
+            index: IndexConfig::default(),
+            plugins: Vec::new(),
+            sideloaded: Vec::new(),
+            restart: RestartPolicy::default(),
+            port_range: PortRange::default(),
+            monitor: MonitorConfig::default(),
+            launch: LaunchOptions::default(),
+            limits: ResourceLimits::default(),
+            token_path: None,
+        };
+        config.store(path).unwrap_or_else(|err| {
+            warn!("Failed to store default config: {}", err);
+        });
+        config
+    }
+
+    /// Changes the config shared by a running dashboard and stores it.
+    pub fn update(
+        shared: &Mutex<Self>,
+        options: &AppOptions,
+        f: impl FnOnce(&mut Self),
+    ) -> std::io::Result<()> {
+        let mut config = shared.lock().unwrap();
+        f(&mut config);
+        config.store(&Self::path(options))
+    }
+
+    pub fn address(&self) -> ServerAddress {
+        ServerAddress {
+            host: "127.0.0.1".to_string(),
+            port: self.port,
+        }
+    }
+
+    pub fn store(&self, path: &PathBuf) -> Result<(), std::io::Error> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)
+    }
+
+    pub fn load(path: &PathBuf) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let config: Self = serde_json::from_str(&content)?;
+        Ok(config)
+    }
+
+    pub fn get_token_path(&self) -> PathBuf {
+        if let Some(token_path) = &self.token_path {
+            return token_path.clone();
+        }
+        let mut token_path = self.workdir.join("token.txt");
+        if cfg!(dev) {
+            token_path = std::env::current_dir()
+                .unwrap()
+                .join("../../../appdata/token.txt");
+        }
+        return token_path;
+    }
+}
+
+/// Returns whether a package is part of the core installation and must not be removed.
+pub fn is_protected_package(name: &str) -> bool {
+    let name = normalize_package_name(name);
+    CORE_PACKAGES.contains(&name.as_str())
+        || REQUIREMENTS
+            .lines()
+            .filter_map(requirement_name)
+            .any(|requirement| requirement == name)
+}
+
+fn generate_hash() -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    let random_bytes: [u8; 16] = rand::random();
+    hasher.update(&random_bytes);
+    format!("{:x}", hasher.finalize())
+}
+
+pub struct ServerProcess {
+    pid: u32,
+    /// Set when the server is stopped on purpose, so the supervisor neither
+    /// restarts it nor reports a crash.
+    stopping: Arc<AtomicBool>,
+    exited: Arc<AtomicBool>,
+    started: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyState {
+    Pending,
+    Ready,
+    TimedOut,
+    Exited,
+}
+
+/// Whether the latest launch of the server accepts connections, see
+/// [`accepts_requests`].
+pub struct Readiness {
+    state: Mutex<ReadyState>,
+    changed: Condvar,
+}
+
+impl Readiness {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(ReadyState::Pending),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn set(&self, state: ReadyState) {
+        *self.state.lock().unwrap() = state;
+        self.changed.notify_all();
+    }
+
+    /// Waits until the readiness check of the current launch has finished.
+    pub fn wait(&self, timeout: Duration) -> ReadyState {
+        let state = self.state.lock().unwrap();
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| *state == ReadyState::Pending)
+            .unwrap();
+        *state
+    }
+}
+
+pub struct Server {
+    config: ServerConfig,
+    base: Python,
+    python: Python,
+    uv: Uv,
+    fallback: Option<Python>,
+    process: Arc<Mutex<Option<ServerProcess>>>,
+    events: Arc<dyn EventSink>,
+    readiness: Arc<Readiness>,
+    logs: Arc<ServerLogs>,
+    stats: Arc<Mutex<Option<ServerStats>>>,
+    launch: Arc<Mutex<LaunchOptions>>,
+    lifecycle: ServerLifecycle,
+    pub token: String,
+    pub already_started: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ServerState {
+    ServerStarting {
+        msg: String,
+    },
+    ServerStarted {
+        msg: String,
+    },
+    /// The server answers on its port.
+    ServerReady {
+        msg: String,
+    },
+    /// The server was started but did not answer within [`HEALTH_CHECK_TIMEOUT`].
+    ServerStartTimeout {
+        msg: String,
+    },
+    ServerRestarting {
+        msg: String,
+    },
+    ServerStopped {
+        msg: String,
+    },
+    /// The server kept crashing and the supervisor gave up restarting it.
+    ServerCrashLoop {
+        msg: String,
+        report: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ServerEnsureProgress {
+    UpdatingDependencies { progress: UvEnsureProgress },
+    DependenciesUpToDate { msg: String },
+    SideloadSkipped { msg: String },
+    ServerStopping { msg: String },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum StartProgress {
+    Python { progress: PythonEnsureProgress },
+    Uv { progress: UvEnsureProgress },
+    Server { progress: ServerEnsureProgress },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum StartError {
+    ServerStartFailed { msg: String },
+    ServerStartTimeout { msg: String },
+    PythonEnsureError { reason: PythonEnsureError },
+    UvEnsureError { reason: UvEnsureError },
+    ServerEnsureError { reason: ServerEnsureError },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum RollbackResult {
+    Restored { msg: String },
+    Failed { reason: Box<UvEnsureError> },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ServerEnsureError {
+    VersionReadFailed {
+        msg: String,
+    },
+    UpdateDependenciesFailed {
+        reason: UvEnsureError,
+    },
+    UpdateFailed {
+        reason: UvEnsureError,
+        rollback: RollbackResult,
+    },
+    StopFailed {
+        msg: String,
+    },
+    UninstallFailed {
+        msg: String,
+    },
+    TokenReadFailed {
+        msg: String,
+    },
+    TokenWriteFailed {
+        msg: String,
+    },
+    CreateDataDirFailed {
+        msg: String,
+    },
+    CreateEnvironmentFailed {
+        msg: String,
+    },
+    StartFailed {
+        msg: String,
+    },
+    PortOccupiedByForeignProcess {
+        msg: String,
+    },
+    AlreadyRunning {
+        msg: String,
+    },
+    TimedOut {
+        msg: String,
+    },
+    InvalidLaunchOptions {
+        msg: String,
+    },
+}
+
+impl Server {
+    /// Installs Python, uv and the server's dependencies and starts the
+    /// server, unless one is running already. Shared by the dashboard and
+    /// the headless mode.
+    ///
+    /// When a program other than omuserver holds the configured port, the
+    /// server moves to a free port of [`ServerConfig::port_range`], which is
+    /// stored to `config` and announced with [`ServerEvent::Address`].
+    pub fn ensure_and_start(
+        options: &AppOptions,
+        app_config: &AppConfig,
+        config: &Mutex<ServerConfig>,
+        force_reinstall: bool,
+        on_progress: impl Fn(StartProgress) + Send + Sync + Clone + 'static,
+        events: Arc<dyn EventSink>,
+        logs: Arc<ServerLogs>,
+    ) -> Result<Self, StartError> {
+        let mut server_config = config.lock().unwrap().clone();
+        let callback = on_progress.clone();
+        let python = Python::ensure(options, move |progress| {
+            callback(StartProgress::Python { progress });
+        })
+        .map_err(|err| StartError::PythonEnsureError { reason: err })?;
+        let callback = on_progress.clone();
+        let uv = Uv::ensure(
+            options,
+            &python.python_bin,
+            &server_config.index,
+            app_config.uv_cache_dir(options).as_deref(),
+            move |progress| {
+                callback(StartProgress::Uv { progress });
+            },
+        )
+        .map_err(|err| StartError::UvEnsureError { reason: err })?;
+        let ensure = |config: &ServerConfig| {
+            let callback = on_progress.clone();
+            Self::ensure_server(
+                config,
+                python.clone(),
+                uv.with_python(&python.python_bin),
+                force_reinstall,
+                move |progress| {
+                    callback(StartProgress::Server { progress });
+                },
+                events.clone(),
+                logs.clone(),
+            )
+            .map(|server| server.with_lifecycle(app_config.server_lifecycle))
+        };
+        let mut server = match ensure(&server_config) {
+            Err(ServerEnsureError::PortOccupiedByForeignProcess { msg }) => {
+                let range = &server_config.port_range;
+                let port = range.find_free(server_config.port).ok_or_else(|| {
+                    StartError::ServerEnsureError {
+                        reason: ServerEnsureError::PortOccupiedByForeignProcess {
+                            msg: format!(
+                                "{}, and no port in {}-{} is free",
+                                msg, range.start, range.end
+                            ),
+                        },
+                    }
+                })?;
+                warn!("{}, moving the server to port {}", msg, port);
+                ServerConfig::update(config, options, |config| config.port = port).map_err(
+                    |err| StartError::ServerStartFailed {
+                        msg: format!("Failed to store server config: {}", err),
+                    },
+                )?;
+                server_config.port = port;
+                events.emit(ServerEvent::Address(server_config.address()));
+                ensure(&server_config)
+            }
+            result => result,
+        }
+        .map_err(|err| StartError::ServerEnsureError { reason: err })?;
+
+        if !server.already_started {
+            server
+                .start()
+                .map_err(|err| StartError::ServerEnsureError { reason: err })?;
+        }
+        Ok(server)
+    }
+
+    pub fn ensure_server(
+        config: &ServerConfig,
+        python: Python,
+        uv: Uv,
+        force_reinstall: bool,
+        on_progress: impl Fn(ServerEnsureProgress) + Send + Sync + Clone + 'static,
+        events: Arc<dyn EventSink>,
+        logs: Arc<ServerLogs>,
+    ) -> Result<Self, ServerEnsureError> {
+        let mut already_started = match identify_port_owner(config.port) {
+            PortOwner::Free => false,
+            PortOwner::Omuserver { version } => {
+                info!("Found omuserver {} on port {}", version, config.port);
+                true
+            }
+            PortOwner::Foreign => {
+                return Err(ServerEnsureError::PortOccupiedByForeignProcess {
+                    msg: format!(
+                        "Port {} is in use by a program that is not omuserver",
+                        config.port
+                    ),
+                })
+            }
+        };
+
+        let version = Self::read_version(&config)?;
+        let needs_update = version.as_deref() != Some(VERSION);
+        let base = python;
+        let mut python = Self::active_environment(&base);
+        let mut uv = uv.with_python(&python.python_bin);
+        let mut fallback = None;
+        // A server left running by an earlier session is supervised again if
+        // its pid file proves it is the process this dashboard started.
+        let mut orphan = already_started
+            .then(|| PidFile::read(&config.workdir))
+            .flatten()
+            .filter(|pid_file| pid_file.port == config.port)
+            .and_then(|pid_file| {
+                let start_time = pid_file.verify(&python)?;
+                Some((pid_file, start_time))
+            });
+        if already_started && needs_update {
+            // Install the new version side by side so the running server keeps
+            // serving until the swap, which happens in `start`.
+            let next = Self::prepare_environment(config, &base, &python, &uv, &on_progress)?;
+            on_progress(ServerEnsureProgress::ServerStopping {
+                msg: format!(
+                    "Server version mismatch ({} != {}), stopping server",
+                    version.unwrap_or("none".to_string()),
+                    VERSION
+                ),
+            });
+            match orphan.take() {
+                Some((pid_file, start_time)) => {
+                    Self::stop_orphan(&python, pid_file.pid, start_time)?;
+                    PidFile::remove(&config.workdir, pid_file.pid);
+                }
+                None => Self::stop_server(&python, config)?,
+            }
+            uv = uv.with_python(&next.python_bin);
+            fallback = Some(std::mem::replace(&mut python, next));
+            already_started = false;
+        } else {
+            Self::update_dependencies(config, &python, &uv, force_reinstall, &on_progress)?;
+        }
+
+        let token = if already_started {
+            Self::read_token(&config)
+                .map_err(|err| ServerEnsureError::TokenReadFailed { msg: err })?
+        } else {
+            Self::generate_token(&config)?
+        };
+
+        let server = Self {
+            config: config.clone(),
+            base,
+            python,
+            uv,
+            fallback,
+            process: Arc::new(Mutex::new(None)),
+            events,
+            readiness: Arc::new(Readiness::new()),
+            logs,
+            stats: Arc::new(Mutex::new(None)),
+            launch: Arc::new(Mutex::new(config.launch.clone())),
+            lifecycle: ServerLifecycle::default(),
+            token,
+            already_started,
+        };
+
+        if !server.config.workdir.exists() {
+            std::fs::create_dir_all(&server.config.workdir).map_err(|err| {
+                ServerEnsureError::CreateDataDirFailed {
+                    msg: format!(
+                        "Failed to create server data directory at {}: {}",
+                        server.config.workdir.display(),
+                        err
+                    ),
+                }
+            })?;
+        }
+
+        if let (true, Some((pid_file, start_time))) = (server.already_started, orphan) {
+            server.attach(pid_file, start_time);
+        }
+
+        Ok(server)
+    }
+
+    /// Returns the environment the server should run from.
+    ///
+    /// This is the last environment promoted by a side-by-side update, or the
+    /// base interpreter when there is none.
+    pub fn active_environment(base: &Python) -> Python {
+        let environments = base.path.join(ENVIRONMENTS_DIR);
+        let Ok(name) = std::fs::read_to_string(environments.join(ACTIVE_ENVIRONMENT_FILE)) else {
+            return base.clone();
+        };
+        let env = base.venv(&environments.join(name.trim()));
+        if env.python_bin.exists() {
+            env
+        } else {
+            warn!(
+                "Active environment {} is missing, using base interpreter",
+                env.path.display()
+            );
+            base.clone()
+        }
+    }
+
+    fn set_active_environment(base: &Python, env: &Python) -> Result<(), String> {
+        let marker = base
+            .path
+            .join(ENVIRONMENTS_DIR)
+            .join(ACTIVE_ENVIRONMENT_FILE);
+        let result = if env.path == base.path {
+            match std::fs::remove_file(&marker) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            }
+        } else {
+            let name = env.path.file_name().unwrap_or_default();
+            std::fs::write(&marker, name.to_string_lossy().as_bytes())
+        };
+        result.map_err(|err| {
+            format!(
+                "Failed to update active environment marker {}: {}",
+                marker.display(),
+                err
+            )
+        })
+    }
+
+    /// Creates a fresh environment for the current version next to the active one
+    /// and installs the requirements into it.
+    ///
+    /// Fails rather than replacing `active`, which a failed promotion can leave
+    /// pointing at the environment for this version while its server runs.
+    fn prepare_environment(
+        config: &ServerConfig,
+        base: &Python,
+        active: &Python,
+        uv: &Uv,
+        on_progress: &(impl Fn(ServerEnsureProgress) + Send + Sync + Clone + 'static),
+    ) -> Result<Python, ServerEnsureError> {
+        let path = base.path.join(ENVIRONMENTS_DIR).join(VERSION);
+        if path == base.path || path == active.path {
+            return Err(ServerEnsureError::CreateEnvironmentFailed {
+                msg: format!(
+                    "Environment at {} is in use by the running server, stop it before updating",
+                    path.display()
+                ),
+            });
+        }
+        let remove_environment = || {
+            remove_dir_all(&path, |_, _| {}).map_err(|err| {
+                ServerEnsureError::CreateEnvironmentFailed {
+                    msg: format!(
+                        "Failed to remove environment at {}: {}",
+                        path.display(),
+                        err
+                    ),
+                }
+            })
+        };
+        remove_environment()?;
+
+        let callback = on_progress.clone();
+        uv.with_python(&base.python_bin)
+            .create_venv(&path, &move |progress| {
+                callback(ServerEnsureProgress::UpdatingDependencies { progress });
+            })
+            .map_err(|err| ServerEnsureError::UpdateDependenciesFailed { reason: err })?;
+        let next = base.venv(&path);
+        let next_uv = uv.with_python(&next.python_bin);
+        if let Err(err) = Self::update_dependencies(config, &next, &next_uv, false, on_progress) {
+            remove_environment()?;
+            return Err(err);
+        }
+        Ok(next)
+    }
+
+    /// Returns the core requirements followed by the user's plugins.
+    ///
+    /// Sideloaded plugins are kept as long as their file is unchanged and they
+    /// do not conflict with another requirement, otherwise they are skipped
+    /// with a warning.
+    fn requirements(
+        config: &ServerConfig,
+        on_progress: &(impl Fn(ServerEnsureProgress) + Send + Sync + Clone + 'static),
+    ) -> String {
+        let mut requirements = REQUIREMENTS.to_string();
+        for plugin in &config.plugins {
+            requirements.push('\n');
+            requirements.push_str(plugin);
+        }
+        for plugin in &config.sideloaded {
+            match Self::check_sideloaded(config, plugin) {
+                Ok(requirement) => {
+                    requirements.push('\n');
+                    requirements.push_str(&requirement);
+                }
+                Err(msg) => {
+                    warn!("{}", msg);
+                    on_progress(ServerEnsureProgress::SideloadSkipped { msg });
+                }
+            }
+        }
+        requirements
+    }
+
+    /// Returns the requirement that installs the sideloaded plugin, or why it
+    /// has to be skipped.
+    fn check_sideloaded(
+        config: &ServerConfig,
+        plugin: &SideloadedPlugin,
+    ) -> Result<String, String> {
+        let name = normalize_package_name(&plugin.name);
+        let conflicts = is_protected_package(&name)
+            || config
+                .plugins
+                .iter()
+                .any(|requirement| requirement_name(requirement).as_deref() == Some(&name));
+        if conflicts {
+            return Err(format!(
+                "Sideloaded plugin {} conflicts with another requirement and was skipped",
+                plugin.name
+            ));
+        }
+        let contents = std::fs::read(&plugin.path).map_err(|err| {
+            format!(
+                "Sideloaded plugin {} at {} could not be read and was skipped: {}",
+                plugin.name,
+                plugin.path.display(),
+                err
+            )
+        })?;
+        if compute_checksum(&contents) != plugin.sha256 {
+            return Err(format!(
+                "Sideloaded plugin {} at {} was modified and was skipped",
+                plugin.name,
+                plugin.path.display()
+            ));
+        }
+        distribution_requirement(&plugin.name, &plugin.path).map_err(|err| {
+            format!(
+                "Sideloaded plugin {} could not be installed and was skipped: {}",
+                plugin.name, err
+            )
+        })
+    }
+
+    /// Removes deprecated packages and installs the current requirements.
+    ///
+    /// A snapshot of the installed packages is taken beforehand, and restored
+    /// if any step fails so the server is never left half updated.
+    ///
+    /// The update is skipped when the environment stamp shows that nothing
+    /// changed since the last successful update, unless `force_reinstall` is set.
+    fn update_dependencies(
+        config: &ServerConfig,
+        python: &Python,
+        uv: &Uv,
+        force_reinstall: bool,
+        on_progress: &(impl Fn(ServerEnsureProgress) + Send + Sync + Clone + 'static),
+    ) -> Result<(), ServerEnsureError> {
+        let requirements = Self::requirements(config, on_progress);
+        let make_stamp = || {
+            let stamp_input = format!(
+                "{}\n{}\n{}\n{}",
+                LATEST_PIP,
+                requirements,
+                DEPRECATED_REQUIREMENTS.as_str(),
+                uv.index.redact(&uv.index.uv_args().join(" "))
+            );
+            EnvironmentStamp::new(&python.path, &stamp_input, &uv.version, &python.version)
+        };
+        if !force_reinstall {
+            if let Some(stamp) = read_environment_stamp(&python.path) {
+                match make_stamp() {
+                    Ok(current) if current == stamp => {
+                        on_progress(ServerEnsureProgress::DependenciesUpToDate {
+                            msg: format!(
+                                "Dependencies at {} are up to date",
+                                python.path.display()
+                            ),
+                        });
+                        return Ok(());
+                    }
+                    Ok(_) => info!("Environment stamp changed, updating dependencies"),
+                    Err(err) => warn!("Failed to compute environment stamp: {}", err),
+                }
+            }
+        }
+        if let Err(err) = remove_environment_stamp(&python.path) {
+            warn!("Failed to remove environment stamp: {}", err);
+        }
+
+        let callback = on_progress.clone();
+        let snapshot = uv
+            .freeze(&move |progress| {
+                callback(ServerEnsureProgress::UpdatingDependencies { progress });
+            })
+            .map_err(|err| ServerEnsureError::UpdateDependenciesFailed { reason: err })?;
+
+        let callback = on_progress.clone();
+        let result = uv
+            .uninstall_requirements(DEPRECATED_REQUIREMENTS.as_str(), &move |progress| {
+                callback(ServerEnsureProgress::UpdatingDependencies { progress });
+            })
+            .and_then(|_| {
+                let callback = on_progress.clone();
+                uv.update(
+                    LATEST_PIP,
+                    &requirements,
+                    force_reinstall,
+                    move |progress| {
+                        callback(ServerEnsureProgress::UpdatingDependencies { progress });
+                    },
+                )
+            });
+        let reason = match result {
+            Ok(()) => {
+                if let Err(err) =
+                    make_stamp().and_then(|stamp| write_environment_stamp(&python.path, &stamp))
+                {
+                    warn!("Failed to write environment stamp: {}", err);
+                }
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+
+        warn!("Failed to update dependencies, rolling back: {:?}", reason);
+        let callback = on_progress.clone();
+        let rollback = match uv.restore(&snapshot, &move |progress| {
+            callback(ServerEnsureProgress::UpdatingDependencies { progress });
+        }) {
+            Ok(()) => RollbackResult::Restored {
+                msg: "Previous requirements were restored".to_string(),
+            },
+            Err(err) => {
+                warn!("Failed to roll back dependencies: {:?}", err);
+                RollbackResult::Failed {
+                    reason: Box::new(err),
+                }
+            }
+        };
+        Err(ServerEnsureError::UpdateFailed { reason, rollback })
+    }
+
+    pub fn stop_server(python: &Python, option: &ServerConfig) -> Result<(), ServerEnsureError> {
+        let mut cmd = python.cmd();
+        cmd.arg("-m");
+        cmd.arg("omuserver");
+        cmd.arg("--port");
+        cmd.arg(option.port.to_string());
+        cmd.arg("--stop");
+        cmd.current_dir(&option.workdir);
+        info!("Stopping server with command: {:?}", cmd);
+        let output = python
+            .run(&mut cmd, STOP_TIMEOUT)
+            .map_err(|err| match err {
+                ProcessError::TimedOut { .. } => ServerEnsureError::TimedOut {
+                    msg: format!("Failed to stop server with command {:?}: {}", cmd, err),
+                },
+                ProcessError::Io(err) => ServerEnsureError::StopFailed {
+                    msg: format!("Failed to stop server with command {:?}: {}", cmd, err),
+                },
+            })?;
+        if !output.status.success() {
+            warn!(
+                "Failed to stop server with command {:?}: exited with code {}: {}",
+                cmd,
+                output.status.code().unwrap_or(-1),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    pub fn uninstall(python: &Python, option: &ServerConfig) -> Result<(), ServerEnsureError> {
+        let mut cmd = python.cmd();
+        cmd.arg("-m");
+        cmd.arg("omuserver");
+        cmd.arg("--port");
+        cmd.arg(option.port.to_string());
+        cmd.arg("--uninstall");
+        cmd.current_dir(&option.workdir);
+        info!("Uninstalling server with command: {:?}", cmd);
+        let output = python
+            .run(&mut cmd, UNINSTALL_TIMEOUT)
+            .map_err(|err| match err {
+                ProcessError::TimedOut { .. } => ServerEnsureError::TimedOut {
+                    msg: format!("Failed to uninstall server with command {:?}: {}", cmd, err),
+                },
+                ProcessError::Io(err) => ServerEnsureError::UninstallFailed {
+                    msg: format!("Failed to uninstall server with command {:?}: {}", cmd, err),
+                },
+            })?;
+        if !output.status.success() {
+            warn!(
+                "Failed to uninstall server with command {:?}: exited with code {}: {}",
+                cmd,
+                output.status.code().unwrap_or(-1),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    pub fn read_version(option: &ServerConfig) -> Result<Option<String>, ServerEnsureError> {
+        let path = option.workdir.join("VERSION");
+        if !path.exists() {
+            return Ok(None);
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(version) => Ok(Some(version)),
+            Err(err) => Err(ServerEnsureError::VersionReadFailed {
+                msg: format!("Failed to read version file at {}: {}", path.display(), err),
+            }),
+        }
+    }
+
+    fn generate_token(option: &ServerConfig) -> Result<String, ServerEnsureError> {
+        let token = generate_token();
+        Self::save_token(&token, option)
+            .map_err(|err| ServerEnsureError::TokenWriteFailed { msg: err })?;
+        Ok(token)
+    }
+
+    fn save_token(token: &str, option: &ServerConfig) -> Result<(), String> {
+        let token_path = option.get_token_path();
+        let parent_dir = token_path.parent().unwrap();
+        std::fs::create_dir_all(&parent_dir).map_err(|err| {
+            format!(
+                "Failed to create token file directory at {}: {}",
+                parent_dir.display(),
+                err
+            )
+        })?;
+        std::fs::write(token_path.clone(), token).map_err(|err| {
+            format!(
+                "Failed to write token file at {}: {}",
+                token_path.display(),
+                err
+            )
+        })?;
+        Ok(())
+    }
+
+    fn read_token(option: &ServerConfig) -> Result<String, String> {
+        let token_path = option.get_token_path();
+        if !token_path.exists() {
+            Err(format!(
+                "Port {} is already in use, but token file does not exist at {}",
+                option.port,
+                token_path.display()
+            ))?;
+        }
+        Ok(match std::fs::read_to_string(&token_path) {
+            Ok(token) => token,
+            Err(_) => Err(format!(
+                "Port {} is already in use, but failed to read token file at {}",
+                option.port,
+                &token_path.display()
+            ))?,
+        })
+    }
+
+    pub fn start(&mut self) -> Result<(), ServerEnsureError> {
+        if self.already_started {
+            return Err(ServerEnsureError::AlreadyRunning {
+                msg: format!("Server is already running on port {}", self.config.port),
+            });
+        }
+        self.spawn(&self.python)?;
+
+        // After a side-by-side update the new environment is only promoted once
+        // the server started from it is healthy.
+        let Some(fallback) = self.fallback.take() else {
+            return Ok(());
+        };
+        let result = self.swap_environment(fallback);
+        // The caller drops a server that failed to start, which would leave
+        // the process running unsupervised.
+        if result.is_err() {
+            if let Err(err) = self.stop() {
+                warn!("Failed to stop server after a failed start: {:?}", err);
+            }
+        }
+        result
+    }
+
+    /// Promotes the environment of the freshly started server, or restarts
+    /// the server from `fallback` if it does not become ready.
+    fn swap_environment(&mut self, fallback: Python) -> Result<(), ServerEnsureError> {
+        let ready = self.readiness.wait(READY_WAIT_TIMEOUT);
+        if ready == ReadyState::Ready {
+            info!("Promoting environment {}", self.python.path.display());
+            Self::set_active_environment(&self.base, &self.python)
+                .map_err(|msg| ServerEnsureError::StartFailed { msg })?;
+            if fallback.path != self.base.path {
+                if let Err(err) = remove_dir_all(&fallback.path, |_, _| {}) {
+                    warn!("Failed to remove previous environment: {}", err);
+                }
+            }
+            return Ok(());
+        }
+
+        warn!(
+            "Server failed its health check, falling back to environment {}",
+            fallback.path.display()
+        );
+        self.stop()?;
+        self.uv = self.uv.with_python(&fallback.python_bin);
+        let failed = std::mem::replace(&mut self.python, fallback);
+        if let Err(err) = remove_dir_all(&failed.path, |_, _| {}) {
+            warn!("Failed to remove failed environment: {}", err);
+        }
+        self.spawn(&self.python)
+    }
+
+    pub fn readiness(&self) -> Arc<Readiness> {
+        self.readiness.clone()
+    }
+
+    pub fn with_lifecycle(mut self, lifecycle: ServerLifecycle) -> Self {
+        self.lifecycle = lifecycle;
+        self
+    }
+
+    /// Replaces the launch options used when the supervisor next relaunches
+    /// the server, without restarting it now.
+    pub fn set_launch_options(&self, launch: LaunchOptions) {
+        *self.launch.lock().unwrap() = launch;
+    }
+
+    fn launcher(&self, python: &Python) -> Launcher {
+        Launcher {
+            python: python.clone(),
+            config: self.config.clone(),
+            index: self.uv.index.clone(),
+            cache_dir: self.uv.managed_cache_dir().map(Path::to_path_buf),
+            readiness: self.readiness.clone(),
+            logs: self.logs.clone(),
+            launch: self.launch.clone(),
+            die_with_parent: self.lifecycle == ServerLifecycle::DieWithParent,
+        }
+    }
+
+    fn spawn(&self, python: &Python) -> Result<(), ServerEnsureError> {
+        let launcher = self.launcher(python);
+        let launched = launcher.spawn(&self.events)?;
+        self.supervise(launcher, launched);
+        Ok(())
+    }
+
+    /// Supervises a server left running by an earlier session, so it can be
+    /// stopped and monitored like one started by this session.
+    fn attach(&self, pid_file: PidFile, start_time: String) {
+        info!("Attaching to server process {}", pid_file.pid);
+        let launched = Launched {
+            child: self.python.attach(pid_file.pid, start_time),
+            pid: pid_file.pid,
+            first_line: self.logs.next_seq(),
+            started: Instant::now(),
+            exited: Arc::new(AtomicBool::new(false)),
+        };
+        self.readiness.set(ReadyState::Ready);
+        self.supervise(self.launcher(&self.python), launched);
+    }
+
+    /// Watches the server process and relaunches it with the same token and
+    /// arguments when it exits with [`RESTART_CODE`] or crashes.
+    ///
+    /// Crashes are restarted with exponential backoff as configured by
+    /// [`RestartPolicy`], each one leaving a report in the logs directory.
+    fn supervise(&self, launcher: Launcher, launched: Launched) {
+        let stopping = Arc::new(AtomicBool::new(false));
+        let mut child = launched.child;
+        let mut first_line = launched.first_line;
+        let mut started = launched.started;
+        let mut exited = launched.exited;
+        let mut pid = launched.pid;
+        *self.process.lock().unwrap() = Some(ServerProcess {
+            pid,
+            stopping: stopping.clone(),
+            exited: exited.clone(),
+            started,
+        });
+        self.monitor(stopping.clone());
+
+        let process_store = self.process.clone();
+        let events = self.events.clone();
+        let policy = launcher.config.restart.clone();
+        let mut crashes = VecDeque::new();
+
+        std::thread::spawn(move || loop {
+            let exit = child.wait();
+            exited.store(true, Ordering::SeqCst);
+            PidFile::remove(&launcher.config.workdir, pid);
+            let status = match exit {
+                Ok(status) => status,
+                Err(err) => {
+                    warn!("Server process exited with error: {}", err);
+                    release(&process_store, &stopping);
+                    if !stopping.load(Ordering::SeqCst) {
+                        emit_state(
+                            &events,
+                            ServerState::ServerStopped {
+                                msg: format!("Server stopped: {}", err),
+                            },
+                        );
+                    }
+                    return;
+                }
+            };
+
+            let relaunch = match status.code() {
+                _ if stopping.load(Ordering::SeqCst) => {
+                    info!("Server stopped with {}", describe_exit(&status));
+                    false
+                }
+                Some(0) => {
+                    info!("Server exited normally");
+                    false
+                }
+                Some(RESTART_CODE) => {
+                    info!("Restarting server");
+                    emit_state(
+                        &events,
+                        ServerState::ServerRestarting {
+                            msg: "Server is restarting".to_string(),
+                        },
+                    );
+                    true
+                }
+                _ => {
+                    let now = Instant::now();
+                    let window = Duration::from_secs(policy.window_secs);
+                    crashes.push_back(now);
+                    while crashes
+                        .front()
+                        .map_or(false, |crash| now.duration_since(*crash) > window)
+                    {
+                        crashes.pop_front();
+                    }
+                    warn!(
+                        "Server crashed with {} after {:.1}s",
+                        describe_exit(&status),
+                        started.elapsed().as_secs_f64()
+                    );
+                    let report = launcher
+                        .write_crash_report(&status, started.elapsed(), first_line)
+                        .map_err(|err| warn!("Failed to write crash report: {}", err))
+                        .ok();
+
+                    if !policy.enabled {
+                        emit_state(
+                            &events,
+                            ServerState::ServerStopped {
+                                msg: format!("Server exited with {}", describe_exit(&status)),
+                            },
+                        );
+                        false
+                    } else if crashes.len() as u32 > policy.max_restarts {
+                        emit_state(
+                            &events,
+                            ServerState::ServerCrashLoop {
+                                msg: format!(
+                                    "Server crashed {} times within {}s, giving up",
+                                    crashes.len(),
+                                    policy.window_secs
+                                ),
+                                report,
+                            },
+                        );
+                        false
+                    } else {
+                        let backoff = policy.backoff(crashes.len() as u32);
+                        emit_state(
+                            &events,
+                            ServerState::ServerRestarting {
+                                msg: format!(
+                                    "Server exited with {}, restarting in {:.1}s",
+                                    describe_exit(&status),
+                                    backoff.as_secs_f64()
+                                ),
+                            },
+                        );
+                        std::thread::sleep(backoff);
+                        !stopping.load(Ordering::SeqCst)
+                    }
+                }
+            };
+            if !relaunch {
+                release(&process_store, &stopping);
+                return;
+            }
+
+            // The process slot stays occupied while relaunching so that
+            // `start_server` does not start a second server meanwhile.
+            match launcher.spawn(&events) {
+                Ok(launched) => {
+                    child = launched.child;
+                    first_line = launched.first_line;
+                    started = launched.started;
+                    exited = launched.exited;
+                    pid = launched.pid;
+                    *process_store.lock().unwrap() = Some(ServerProcess {
+                        pid,
+                        stopping: stopping.clone(),
+                        exited: exited.clone(),
+                        started,
+                    });
+                }
+                Err(err) => {
+                    warn!("Failed to restart server: {:?}", err);
+                    release(&process_store, &stopping);
+                    emit_state(
+                        &events,
+                        ServerState::ServerStopped {
+                            msg: "Server failed to restart".to_string(),
+                        },
+                    );
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Samples the resource usage of the supervised process every
+    /// [`MonitorConfig::interval_secs`] until the supervisor gives up its slot.
+    fn monitor(&self, stopping: Arc<AtomicBool>) {
+        let config = self.config.monitor.clone();
+        if config.interval_secs == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(config.interval_secs);
+        let process_store = self.process.clone();
+        let python = self.python.clone();
+        let events = self.events.clone();
+        let stats_store = self.stats.clone();
+        let mut previous: Option<(u32, Duration, Instant)> = None;
+        let mut warned = Vec::new();
+
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            let (pid, started) = match process_store.lock().unwrap().as_ref() {
+                Some(process) if Arc::ptr_eq(&process.stopping, &stopping) => {
+                    if process.exited.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    (process.pid, process.started)
+                }
+                _ => return,
+            };
+            let Some(sample) = python.stats(pid) else {
+                continue;
+            };
+            let now = Instant::now();
+            let cpu_percent = match previous {
+                Some((last_pid, cpu_time, at)) if last_pid == pid => {
+                    let elapsed = now.duration_since(at).as_secs_f64();
+                    let used = sample.cpu_time.saturating_sub(cpu_time).as_secs_f64();
+                    if elapsed > 0.0 {
+                        used / elapsed * 100.0
+                    } else {
+                        0.0
+                    }
+                }
+                _ => 0.0,
+            };
+            previous = Some((pid, sample.cpu_time, now));
+
+            let mut warnings = Vec::new();
+            let rss_mb = sample.rss_bytes / (1024 * 1024);
+            if let Some(max) = config.max_rss_mb.filter(|max| rss_mb > *max) {
+                warnings.push(format!("Memory usage of {} MB exceeds {} MB", rss_mb, max));
+            }
+            if let Some(max) = config.max_cpu_percent.filter(|max| cpu_percent > *max) {
+                warnings.push(format!(
+                    "CPU usage of {:.0}% exceeds {:.0}%",
+                    cpu_percent, max
+                ));
+            }
+            // Only log when a threshold starts being exceeded, not every sample.
+            if warnings.len() > warned.len() {
+                for warning in &warnings {
+                    warn!("Server process {}: {}", pid, warning);
+                }
+            }
+            warned = warnings.clone();
+
+            let stats = ServerStats {
+                pid,
+                cpu_percent,
+                rss_bytes: sample.rss_bytes,
+                threads: sample.threads,
+                open_files: sample.open_files,
+                uptime_secs: started.elapsed().as_secs(),
+                warnings,
+            };
+            *stats_store.lock().unwrap() = Some(stats.clone());
+            events.emit(ServerEvent::Stats(stats));
+        });
+    }
+
+    /// The latest resource usage sample of the running server, if any.
+    pub fn stats(&self) -> Option<ServerStats> {
+        let pid = self.process.lock().unwrap().as_ref()?.pid;
+        self.stats
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|stats| stats.pid == pid)
+    }
+
+    /// Stops the server process started by this dashboard, killing it if it
+    /// does not exit within [`STOP_GRACE_PERIOD`]. On Windows it is killed
+    /// right away, see [`Signal::Terminate`].
+    ///
+    /// Returns `false` when there is no such process. Servers left over from
+    /// a previous run are stopped with [`Server::stop_server`] instead.
+    pub fn stop(&self) -> Result<bool, ServerEnsureError> {
+        let (pid, exited) = match self.process.lock().unwrap().as_ref() {
+            Some(process) => {
+                process.stopping.store(true, Ordering::SeqCst);
+                (process.pid, process.exited.clone())
+            }
+            None => return Ok(false),
+        };
+        if exited.load(Ordering::SeqCst) {
+            return Ok(true);
+        }
+
+        terminate(&self.python, pid, &exited)?;
+        Ok(true)
+    }
+
+    /// Stops a server left running by an earlier session, which the pid file
+    /// proved to be ours, like [`Server::stop`] rather than through
+    /// `omuserver --stop`.
+    fn stop_orphan(python: &Python, pid: u32, start_time: String) -> Result<(), ServerEnsureError> {
+        let exited = Arc::new(AtomicBool::new(false));
+        let mut process = python.attach(pid, start_time);
+        let flag = exited.clone();
+        std::thread::spawn(move || {
+            let _ = process.wait();
+            flag.store(true, Ordering::SeqCst);
+        });
+        terminate(python, pid, &exited)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.process.lock().unwrap().is_some()
+    }
+}
+
+struct Launched {
+    child: Box<dyn ChildProcess>,
+    pid: u32,
+    /// `seq` of the first log line of this launch.
+    first_line: u64,
+    started: Instant,
+    /// Set by the supervisor once the process has exited.
+    exited: Arc<AtomicBool>,
+}
+
+/// Everything needed to launch omuserver, kept by the supervisor to relaunch it.
+struct Launcher {
+    python: Python,
+    config: ServerConfig,
+    index: PackageIndex,
+    cache_dir: Option<PathBuf>,
+    readiness: Arc<Readiness>,
+    logs: Arc<ServerLogs>,
+    /// Shared with [`Server`] so that changes apply to relaunches as well.
+    launch: Arc<Mutex<LaunchOptions>>,
+    /// Servers are spawned from command threads of the async runtime or from
+    /// their supervisor, both of which outlive them, see [`die_with_parent`].
+    die_with_parent: bool,
+}
+
+impl Launcher {
+    fn command(&self, launch: &LaunchOptions) -> Command {
+        let env = ProcessEnv::default().with_limits(self.config.limits.clone());
+        let mut cmd = self.python.cmd_with(&env);
+        cmd.arg("-m").arg("omuserver");
+        cmd.arg("--token-file").arg(self.config.get_token_path());
+        cmd.arg("--port").arg(self.config.port.to_string());
+        let hash = if cfg!(dev) {
+            &"dev".to_string()
+        } else {
+            &self.config.hash
+        };
+        cmd.arg("--hash").arg(&hash);
+        let executable = canonicalize(std::env::current_exe().unwrap())
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        info!("Executable: {}", executable);
+        cmd.arg("--dashboard-path").arg(executable);
+
+        cmd.args(self.index.server_args());
+        if launch.debug {
+            cmd.arg("--debug");
+        }
+        cmd.args(&launch.extra_args);
+        cmd.envs(&launch.extra_env);
+        cmd.envs(self.index.envs());
+        if let Some(cache_dir) = &self.cache_dir {
+            cmd.env("UV_CACHE_DIR", cache_dir);
+        }
+        cmd.current_dir(&self.config.workdir);
+        if self.die_with_parent {
+            die_with_parent(&mut cmd);
+        }
+        cmd
+    }
+
+    fn spawn(&self, events: &Arc<dyn EventSink>) -> Result<Launched, ServerEnsureError> {
+        let launch = self.launch.lock().unwrap().clone();
+        launch
+            .validate()
+            .map_err(|msg| ServerEnsureError::InvalidLaunchOptions { msg })?;
+        let mut cmd = self.command(&launch);
+        match self.logs.start_run(&self.config.workdir, &self.index) {
+            Ok(path) => info!("Logging server output to {}", path.display()),
+            Err(err) => warn!("Failed to create server log file: {}", err),
+        }
+        let first_line = self.logs.next_seq();
+        let redact = |text: String| launch.redact(&self.index.redact(&text));
+        info!(
+            "Starting server with args: {} in {:?}",
+            redact(format!("{:?}", cmd)),
+            self.config.workdir
+        );
+        info!("Launch options: {}", launch.describe());
+        emit_state(
+            events,
+            ServerState::ServerStarting {
+                msg: redact(format!("Starting server: {:?}", cmd)),
+            },
+        );
+        let mut child =
+            self.python
+                .spawn(&mut cmd)
+                .map_err(|err| ServerEnsureError::StartFailed {
+                    msg: format!("Failed to start server process: {}", err),
+                })?;
+        let started = Instant::now();
+        info!("Server process started with pid {}", child.id());
+        let info = self.python.inspect(child.id());
+        let pid_file = PidFile {
+            pid: child.id(),
+            start_time: info.as_ref().map(|info| info.start_time.clone()),
+            executable: info.and_then(|info| info.executable),
+            port: self.config.port,
+            version: VERSION.to_string(),
+        };
+        if let Err(err) = pid_file.write(&self.config.workdir) {
+            warn!("Failed to write server pid file: {}", err);
+        }
+
+        let streams = [
+            (LogStream::Stdout, child.take_stdout()),
+            (LogStream::Stderr, child.take_stderr()),
+        ];
+        for (stream, pipe) in streams.into_iter() {
+            let pipe = match pipe {
+                Some(pipe) => pipe,
+                None => continue,
+            };
+            let logs = self.logs.clone();
+            let events = events.clone();
+            std::thread::spawn(move || {
+                // Read raw bytes so output in a non-UTF-8 console code page is
+                // kept (lossily) rather than dropped.
+                let reader = std::io::BufReader::new(pipe);
+                for mut bytes in reader.split(b'\n').map_while(Result::ok) {
+                    if bytes.last() == Some(&b'\r') {
+                        bytes.pop();
+                    }
+                    let line = String::from_utf8_lossy(&bytes).into_owned();
+                    let line = logs.push(stream, line);
+                    log!(line.level.as_log_level(), "{}", line.line);
+                    events.emit(ServerEvent::Log(line));
+                }
+            });
+        }
+        emit_state(
+            events,
+            ServerState::ServerStarted {
+                msg: format!("Server started with pid {}", child.id()),
+            },
+        );
+        let exited = Arc::new(AtomicBool::new(false));
+        self.watch_readiness(events, &exited);
+        Ok(Launched {
+            pid: child.id(),
+            child,
+            first_line,
+            started,
+            exited,
+        })
+    }
+
+    /// Polls the port until the server answers, it exits or
+    /// [`HEALTH_CHECK_TIMEOUT`] expires.
+    fn watch_readiness(&self, events: &Arc<dyn EventSink>, exited: &Arc<AtomicBool>) {
+        self.readiness.set(ReadyState::Pending);
+        let port = self.config.port;
+        let readiness = self.readiness.clone();
+        let events = events.clone();
+        let exited = exited.clone();
+        std::thread::spawn(move || {
+            let deadline = Instant::now() + HEALTH_CHECK_TIMEOUT;
+            let state = loop {
+                if exited.load(Ordering::SeqCst) {
+                    break ReadyState::Exited;
+                }
+                if accepts_requests(port) {
+                    break ReadyState::Ready;
+                }
+                if Instant::now() >= deadline {
+                    break ReadyState::TimedOut;
+                }
+                std::thread::sleep(Duration::from_millis(250));
+            };
+            readiness.set(state);
+            match state {
+                ReadyState::Ready => emit_state(
+                    &events,
+                    ServerState::ServerReady {
+                        msg: format!("Server is ready on port {}", port),
+                    },
+                ),
+                ReadyState::TimedOut => {
+                    warn!("Server did not answer on port {}", port);
+                    emit_state(
+                        &events,
+                        ServerState::ServerStartTimeout {
+                            msg: format!(
+                                "Server did not answer on port {} within {}s",
+                                port,
+                                HEALTH_CHECK_TIMEOUT.as_secs()
+                            ),
+                        },
+                    )
+                }
+                // The supervisor reports why the server exited.
+                ReadyState::Exited | ReadyState::Pending => {}
+            }
+        });
+    }
+
+    /// Saves the exit status, uptime and last output of a crashed server to
+    /// the logs directory.
+    fn write_crash_report(
+        &self,
+        status: &Exit,
+        uptime: Duration,
+        first_line: u64,
+    ) -> std::io::Result<PathBuf> {
+        let dir = log_dir(&self.config.workdir);
+        std::fs::create_dir_all(&dir)?;
+        let now = chrono::Local::now();
+        let path = dir.join(now.format("crash-%Y%m%d-%H%M%S-%3f.log").to_string());
+        let mut report = format!(
+            "Time: {}\nVersion: {}\nExit: {}\nUptime: {:.1}s\n\n",
+            now.to_rfc3339(),
+            VERSION,
+            describe_exit(status),
+            uptime.as_secs_f64()
+        );
+        let output = self.logs.query(&LogFilter {
+            since: Some(first_line),
+            limit: Some(CRASH_REPORT_LINES),
+            ..Default::default()
+        });
+        for line in output {
+            if line.stream == LogStream::Stderr {
+                report.push_str("[stderr] ");
+            }
+            report.push_str(&self.index.redact(&line.line));
+            report.push('\n');
+        }
+        std::fs::write(&path, report)?;
+        info!("Crash report written to {}", path.display());
+        Ok(path)
+    }
+}
+
+/// Empties the process slot unless another supervisor has taken it over,
+/// which happens when the server is stopped and started again.
+fn release(process_store: &Mutex<Option<ServerProcess>>, stopping: &Arc<AtomicBool>) {
+    let mut process = process_store.lock().unwrap();
+    if matches!(process.as_ref(), Some(process) if Arc::ptr_eq(&process.stopping, stopping)) {
+        *process = None;
+    }
+}
+
+/// Asks the process to exit and kills it if it is still running after
+/// [`STOP_GRACE_PERIOD`]. `exited` is set by whoever waits on the process.
+fn terminate(python: &Python, pid: u32, exited: &AtomicBool) -> Result<(), ServerEnsureError> {
+    info!("Stopping server process {}", pid);
+    if let Err(err) = python.signal(pid, Signal::Terminate) {
+        warn!("Failed to signal server process {}: {}", pid, err);
+    }
+    if wait_for_exit(exited, STOP_GRACE_PERIOD) {
+        return Ok(());
+    }
+
+    warn!(
+        "Server process {} did not exit within {}s, killing it",
+        pid,
+        STOP_GRACE_PERIOD.as_secs()
+    );
+    python
+        .signal(pid, Signal::Kill)
+        .map_err(|err| ServerEnsureError::StopFailed {
+            msg: format!("Failed to kill server process {}: {}", pid, err),
+        })?;
+    if !wait_for_exit(exited, STOP_GRACE_PERIOD) {
+        return Err(ServerEnsureError::TimedOut {
+            msg: format!("Server process {} did not exit after being killed", pid),
+        });
+    }
+    Ok(())
+}
+
+fn wait_for_exit(exited: &AtomicBool, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !exited.load(Ordering::SeqCst) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    true
+}
+
+fn emit_state(events: &Arc<dyn EventSink>, state: ServerState) {
+    events.emit(ServerEvent::State(state));
+}
+
+fn describe_exit(exit: &Exit) -> String {
+    let status = match exit {
+        Exit::Status(status) => status,
+        Exit::Unknown => return "unknown status".to_string(),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("signal {}", signal);
+        }
+    }
+    match status.code() {
+        Some(code) => format!("code {}", code),
+        None => "unknown status".to_string(),
+    }
+}
+
+fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>()
+}
+
+const LOOPBACK: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::LOCALHOST),
+    IpAddr::V6(Ipv6Addr::LOCALHOST),
+];
+
+enum PortOwner {
+    Free,
+    Omuserver { version: String },
+    Foreign,
+}
+
+/// Sends a bare HTTP request and returns the status code and body of the response.
+fn http_get(addr: SocketAddr, path: &str) -> Option<(u16, String)> {
+    let mut stream = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(PROBE_TIMEOUT)).ok()?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, addr
+    );
+    stream.write_all(request.as_bytes()).ok()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head.strip_prefix("HTTP/")?.split_whitespace().nth(1)?;
+    Some((status.parse().ok()?, body.to_string()))
+}
+
+/// The version in a response to `/version`, `None` if omuserver did not send it.
+fn parse_version(status: u16, body: &str) -> Option<String> {
+    if status != 200 {
+        return None;
+    }
+    let body: serde_json::Value = serde_json::from_str(body).ok()?;
+    Some(body.get("version")?.as_str()?.to_string())
+}
+
+/// The version reported by the omuserver listening on the loopback address.
+fn server_version(addr: SocketAddr) -> Option<String> {
+    let (status, body) = http_get(addr, "/version")?;
+    parse_version(status, &body)
+}
+
+/// Whether an HTTP server answers on the port.
+///
+/// The websocket handshake is not attempted, omuserver only checks the token
+/// once a client sends its connect packet.
+fn accepts_requests(port: u16) -> bool {
+    LOOPBACK
+        .iter()
+        .any(|ip| http_get(SocketAddr::new(*ip, port), "/version").is_some())
+}
+
+/// The version of the omuserver answering on `port`, `None` if none does.
+pub fn running_version(port: u16) -> Option<String> {
+    LOOPBACK
+        .iter()
+        .find_map(|ip| server_version(SocketAddr::new(*ip, port)))
+}
+
+/// Tells an omuserver holding the port apart from any other program.
+///
+/// A program that answers HTTP other than omuserver does is foreign right
+/// away. One that accepts connections without answering may be an omuserver
+/// still starting up, so it is given [`IDENTIFY_TIMEOUT`] to do so.
+fn identify_port_owner(port: u16) -> PortOwner {
+    let deadline = Instant::now() + IDENTIFY_TIMEOUT;
+    loop {
+        if is_port_free(port) {
+            return PortOwner::Free;
+        }
+        let responses = LOOPBACK
+            .iter()
+            .filter_map(|ip| http_get(SocketAddr::new(*ip, port), "/version"))
+            .collect::<Vec<_>>();
+        if let Some(version) = responses
+            .iter()
+            .find_map(|(status, body)| parse_version(*status, body))
+        {
+            return PortOwner::Omuserver { version };
+        }
+        if !responses.is_empty() || Instant::now() >= deadline {
+            return PortOwner::Foreign;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn is_port_free(port: u16) -> bool {
+    let free = |ip: IpAddr| match std::net::TcpListener::bind((ip, port)) {
+        Ok(_) => true,
+        // Hosts without IPv6 have no ::1 to occupy.
+        Err(err) => ip.is_ipv6() && err.kind() == std::io::ErrorKind::AddrNotAvailable,
+    };
+    free(Ipv4Addr::UNSPECIFIED.into()) && LOOPBACK.iter().all(|ip| free(*ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        diagnostics::UvFailure,
+        events::NoEvents,
+        logs::SERVER_LOG_LINES,
+        process::{
+            fake::{FakeResult, FakeRunner},
+            ProcessInfo,
+        },
+        sources::{
+            py::{PythonVersion, PythonVersionRequest},
+            uv::{UvDownload, UvRequest},
+        },
+        sync::write_venv_marker,
+    };
+
+    struct Fixture {
+        _dir: TempDir,
+        runner: Arc<FakeRunner>,
+        config: ServerConfig,
+        python: Python,
+        uv: Uv,
+    }
+
+    impl Fixture {
+        /// Sets up an installed Python and uv whose processes are faked.
+        fn new(port: u16, version: &str) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let options = AppOptions {
+                python_version: "3.12.3".parse::<PythonVersionRequest>().unwrap(),
+                python_path: dir.path().join("python"),
+                uv_path: dir.path().join("uv"),
+                workdir: dir.path().join("data"),
+                appdir: dir.path().to_path_buf(),
+            };
+            let python_path = options.get_python_path();
+            std::fs::create_dir_all(&python_path).unwrap();
+            let python_version = PythonVersion::try_from(options.python_version.clone()).unwrap();
+            write_venv_marker(&python_path, &python_version).unwrap();
+            let uv_dir = options.uv_path.join(
+                UvDownload::try_from(UvRequest::default())
+                    .unwrap()
+                    .version(),
+            );
+            std::fs::create_dir_all(&uv_dir).unwrap();
+            let uv_name = if cfg!(target_os = "windows") {
+                "uv.exe"
+            } else {
+                "uv"
+            };
+            std::fs::write(uv_dir.join(uv_name), "").unwrap();
+            std::fs::create_dir_all(&options.workdir).unwrap();
+            std::fs::write(options.workdir.join("VERSION"), version).unwrap();
+
+            let runner = FakeRunner::new();
+            let python = Python::ensure(&options, |_| {})
+                .unwrap()
+                .with_runner(runner.clone());
+            let uv = Uv::ensure(
+                &options,
+                &python.python_bin,
+                &IndexConfig::default(),
+                Some(&options.get_uv_cache_dir()),
+                |_| {},
+            )
+            .unwrap()
+            .with_runner(runner.clone());
+            let config = ServerConfig {
+                workdir: options.workdir.clone(),
+                port,
+                hash: "hash".to_string(),
+                index: IndexConfig::default(),
+                plugins: Vec::new(),
+                sideloaded: Vec::new(),
+                restart: RestartPolicy::default(),
+                port_range: PortRange::default(),
+                monitor: MonitorConfig::default(),
+                launch: LaunchOptions::default(),
+                limits: ResourceLimits::default(),
+                // The dev build writes the token to the repository otherwise.
+                token_path: Some(options.workdir.join("token.txt")),
+            };
+            Self {
+                _dir: dir,
+                runner,
+                config,
+                python,
+                uv,
+            }
+        }
+
+        fn ensure_server(&self) -> Result<Server, ServerEnsureError> {
+            Server::ensure_server(
+                &self.config,
+                self.python.clone(),
+                self.uv.with_python(&self.python.python_bin),
+                false,
+                |_| {},
+                Arc::new(NoEvents),
+                Arc::new(ServerLogs::new(SERVER_LOG_LINES)),
+            )
+        }
+    }
+
+    fn free_port() -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    fn respond(stream: &mut TcpStream, response: &str) {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(read) => request.extend_from_slice(&buffer[..read]),
+            }
+        }
+        let _ = stream.write_all(response.as_bytes());
+    }
+
+    /// Answers on the listener like the `/version` route of omuserver.
+    fn serve_version(listener: TcpListener, version: &str) {
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{{\"version\": \"{}\"}}",
+            version
+        );
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().filter_map(Result::ok) {
+                respond(&mut stream, &response);
+            }
+        });
+    }
+
+    #[test]
+    fn test_accepts_requests() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let responder = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            respond(&mut stream, "HTTP/1.1 400 Bad Request\r\n\r\n");
+        });
+        assert!(accepts_requests(port));
+        responder.join().unwrap();
+        assert!(!accepts_requests(port));
+    }
+
+    #[test]
+    fn test_detects_running_server() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let fixture = Fixture::new(listener.local_addr().unwrap().port(), VERSION);
+        serve_version(listener, VERSION);
+        std::fs::write(fixture.config.get_token_path(), "running-token").unwrap();
+
+        let mut server = fixture.ensure_server().unwrap();
+        assert!(server.already_started);
+        assert_eq!(server.token, "running-token");
+        assert!(matches!(
+            server.start(),
+            Err(ServerEnsureError::AlreadyRunning { .. })
+        ));
+        assert!(!fixture.runner.called("--stop"));
+        assert!(!fixture.runner.called("--token-file"));
+    }
+
+    #[test]
+    fn test_version_mismatch_updates_side_by_side() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let fixture = Fixture::new(listener.local_addr().unwrap().port(), "0.0.0");
+        serve_version(listener, "0.0.0");
+
+        let server = fixture.ensure_server().unwrap();
+        assert!(!server.already_started);
+        assert_eq!(
+            server.python.path,
+            fixture.python.path.join(ENVIRONMENTS_DIR).join(VERSION)
+        );
+        assert_eq!(
+            server.fallback.as_ref().map(|python| python.path.clone()),
+            Some(fixture.python.path.clone())
+        );
+        let calls = fixture.runner.calls();
+        let venv = calls.iter().position(|call| call.starts_with("venv"));
+        let stop = calls.iter().position(|call| call.contains("--stop"));
+        assert!(venv.is_some() && stop.is_some() && venv < stop);
+    }
+
+    #[test]
+    fn test_version_mismatch_signals_owned_orphan() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let fixture = Fixture::new(port, "0.0.0");
+        serve_version(listener, "0.0.0");
+        PidFile {
+            pid: 4242,
+            start_time: Some("1000".to_string()),
+            executable: None,
+            port,
+            version: "0.0.0".to_string(),
+        }
+        .write(&fixture.config.workdir)
+        .unwrap();
+        fixture.runner.add_process(
+            4242,
+            ProcessInfo {
+                executable: None,
+                start_time: "1000".to_string(),
+            },
+        );
+
+        let server = fixture.ensure_server().unwrap();
+        assert!(!server.already_started && !server.is_running());
+        assert!(fixture.runner.called("signal Terminate 4242"));
+        assert!(!fixture.runner.called("--stop"));
+        assert!(PidFile::read(&fixture.config.workdir).is_none());
+    }
+
+    #[test]
+    fn test_update_keeps_active_environment() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let fixture = Fixture::new(listener.local_addr().unwrap().port(), "0.0.0");
+        serve_version(listener, "0.0.0");
+        // A promotion that failed after switching environments.
+        let environments = fixture.python.path.join(ENVIRONMENTS_DIR);
+        let active = fixture.python.venv(&environments.join(VERSION));
+        std::fs::create_dir_all(active.python_bin.parent().unwrap()).unwrap();
+        std::fs::write(&active.python_bin, "").unwrap();
+        std::fs::write(environments.join(ACTIVE_ENVIRONMENT_FILE), VERSION).unwrap();
+
+        assert!(matches!(
+            fixture.ensure_server(),
+            Err(ServerEnsureError::CreateEnvironmentFailed { .. })
+        ));
+        assert!(active.python_bin.exists());
+        assert!(!fixture.runner.called("--stop"));
+    }
+
+    #[test]
+    fn test_attaches_to_owned_orphan() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let fixture = Fixture::new(port, VERSION);
+        serve_version(listener, VERSION);
+        std::fs::write(fixture.config.get_token_path(), "running-token").unwrap();
+        let info = ProcessInfo {
+            executable: None,
+            start_time: "1000".to_string(),
+        };
+        let pid_file = PidFile {
+            pid: 4242,
+            start_time: Some("1000".to_string()),
+            executable: None,
+            port,
+            version: VERSION.to_string(),
+        };
+        pid_file.write(&fixture.config.workdir).unwrap();
+
+        // The pid was reused by another process.
+        fixture.runner.add_process(
+            4242,
+            ProcessInfo {
+                start_time: "2000".to_string(),
+                ..info.clone()
+            },
+        );
+        let server = fixture.ensure_server().unwrap();
+        assert!(server.already_started && !server.is_running());
+
+        fixture.runner.add_process(4242, info);
+        let server = fixture.ensure_server().unwrap();
+        assert!(server.is_running());
+        assert!(server.stop().unwrap());
+        assert!(fixture.runner.called("signal Terminate 4242"));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.is_running() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(PidFile::read(&fixture.config.workdir).is_none());
+    }
+
+    #[test]
+    fn test_relaunches_attached_server_after_crash() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut fixture = Fixture::new(port, VERSION);
+        fixture.config.restart = RestartPolicy {
+            initial_backoff_ms: 5,
+            ..Default::default()
+        };
+        serve_version(listener, VERSION);
+        std::fs::write(fixture.config.get_token_path(), "running-token").unwrap();
+        let start_time = "1000".to_string();
+        PidFile {
+            pid: 4242,
+            start_time: Some(start_time.clone()),
+            executable: None,
+            port,
+            version: VERSION.to_string(),
+        }
+        .write(&fixture.config.workdir)
+        .unwrap();
+        fixture.runner.add_process(
+            4242,
+            ProcessInfo {
+                executable: None,
+                start_time,
+            },
+        );
+
+        let server = fixture.ensure_server().unwrap();
+        assert!(server.is_running());
+        assert!(!fixture.runner.called("--token-file"));
+        fixture.runner.remove_process(4242);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !fixture.runner.called("--token-file") && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(fixture.runner.called("--token-file"));
+        let reports = std::fs::read_dir(log_dir(&fixture.config.workdir))
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("crash-"))
+            .map(|entry| std::fs::read_to_string(entry.path()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].contains("Exit: unknown status"));
+    }
+
+    #[test]
+    fn test_rejects_foreign_port_owner() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let fixture = Fixture::new(listener.local_addr().unwrap().port(), VERSION);
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().filter_map(Result::ok) {
+                respond(&mut stream, "HTTP/1.1 404 Not Found\r\n\r\n");
+            }
+        });
+        std::fs::write(fixture.config.get_token_path(), "stale-token").unwrap();
+
+        assert!(matches!(
+            fixture.ensure_server(),
+            Err(ServerEnsureError::PortOccupiedByForeignProcess { .. })
+        ));
+        assert!(!fixture.runner.called("--stop"));
+    }
+
+    #[test]
+    fn test_failed_update_restores_snapshot() {
+        let fixture = Fixture::new(free_port(), VERSION);
+        fixture
+            .runner
+            .script(&["freeze"], FakeResult::stdout("omuserver==0.1.0\n"));
+        fixture.runner.script(
+            &["install", "-r"],
+            FakeResult::stderr(
+                1,
+                "  × No solution found when resolving dependencies:\n  ╰─▶ Because omuserver==0.2.0 depends on aiohttp==3.9.0 and you require omuserver==0.2.0, we can conclude that your requirements are unsatisfiable.",
+            ),
+        );
+
+        match fixture.ensure_server() {
+            Err(ServerEnsureError::UpdateFailed {
+                reason: UvEnsureError::UpdateRequirementsFailed { failure, .. },
+                rollback: RollbackResult::Restored { .. },
+            }) => assert!(matches!(failure, UvFailure::ResolutionConflict { .. })),
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(_) => panic!("update should have failed"),
+        }
+        assert!(fixture.runner.called("pip sync"));
+    }
+
+    #[test]
+    fn test_restart_code_relaunches_server() {
+        let fixture = Fixture::new(free_port(), VERSION);
+        fixture
+            .runner
+            .script(&["--token-file"], FakeResult::exit(RESTART_CODE));
+
+        let mut server = fixture.ensure_server().unwrap();
+        let updated = fixture.runner.calls().len();
+        server.start().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.is_running() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!server.is_running());
+        // Only the two launches, the dependencies are not updated again.
+        let launches = fixture.runner.calls().split_off(updated);
+        assert_eq!(launches.len(), 2);
+        assert!(launches[0].contains("--token-file"));
+        assert_eq!(launches[0], launches[1]);
+    }
+
+    #[test]
+    fn test_crash_loop_gives_up_with_reports() {
+        let mut fixture = Fixture::new(free_port(), VERSION);
+        fixture.config.restart = RestartPolicy {
+            max_restarts: 2,
+            initial_backoff_ms: 5,
+            ..Default::default()
+        };
+        for _ in 0..3 {
+            fixture
+                .runner
+                .script(&["--token-file"], FakeResult::stderr(1, "Traceback"));
+        }
+
+        let mut server = fixture.ensure_server().unwrap();
+        let updated = fixture.runner.calls().len();
+        server.start().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.is_running() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!server.is_running());
+        assert_eq!(fixture.runner.calls().split_off(updated).len(), 3);
+        let reports = std::fs::read_dir(log_dir(&fixture.config.workdir))
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("crash-"))
+            .collect::<Vec<_>>();
+        assert_eq!(reports.len(), 3);
+        let report = std::fs::read_to_string(reports[0].path()).unwrap();
+        assert!(report.contains("Exit: code 1"));
+    }
+
+    #[test]
+    fn test_launch_options_validation() {
+        let mut launch = LaunchOptions {
+            debug: true,
+            extra_args: vec!["--trusted-host".to_string(), "localhost".to_string()],
+            extra_env: BTreeMap::from([("PYTHONTRACEMALLOC".to_string(), "5".to_string())]),
+        };
+        assert!(launch.validate().is_ok());
+
+        launch.extra_args.push("--port=8000".to_string());
+        assert!(launch.validate().is_err());
+        launch.extra_args.pop();
+
+        launch
+            .extra_env
+            .insert("uv_cache_dir".to_string(), "/tmp".to_string());
+        assert!(launch.validate().is_err());
+
+        launch
+            .extra_env
+            .insert("API_KEY".to_string(), "s3cret\"key".to_string());
+        assert!(!launch.describe().contains("s3cret"));
+        let mut cmd = Command::new("python");
+        cmd.envs(&launch.extra_env);
+        assert!(!launch.redact(&format!("{:?}", cmd)).contains("s3cret"));
+    }
+}
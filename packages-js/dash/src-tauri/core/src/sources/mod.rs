@@ -0,0 +1,2 @@
+pub mod py;
+pub mod uv;
@@ -0,0 +1,126 @@
+// https://github.com/astral-sh/rye/blob/main/rye/src/sync.rs - MIT licensed
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::sources::py::PythonVersion;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct VenvMarker {
+    pub python: PythonVersion,
+    pub venv_path: Option<PathBuf>,
+}
+
+impl VenvMarker {
+    pub fn is_compatible(&self, py_ver: &PythonVersion) -> bool {
+        self.python == *py_ver
+    }
+}
+
+pub fn read_venv_marker(venv_path: &Path) -> Option<VenvMarker> {
+    let marker_file = venv_path.join("rye-venv.json");
+    let contents = fs::read(marker_file).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+pub fn write_venv_marker(venv_path: &Path, py_ver: &PythonVersion) -> Result<(), Error> {
+    let marker = venv_path.join("rye-venv.json");
+    fs::write(
+        &marker,
+        serde_json::to_string_pretty(&VenvMarker {
+            python: py_ver.clone(),
+            venv_path: Some(venv_path.into()),
+        })?,
+    )
+    .unwrap();
+
+    Ok(())
+}
+
+/// Directory inside an interpreter that holds side-by-side environments.
+pub const ENVIRONMENTS_DIR: &str = "envs";
+const ENVIRONMENT_STAMP: &str = "omu-stamp.json";
+
+/// Records what was last installed into an environment, so that an unchanged
+/// environment can skip the dependency update.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EnvironmentStamp {
+    pub requirements: String,
+    pub uv_version: String,
+    pub python_version: String,
+    pub records: String,
+}
+
+impl EnvironmentStamp {
+    pub fn new(
+        env_path: &Path,
+        requirements: &str,
+        uv_version: &str,
+        python_version: &PythonVersion,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            requirements: hash_bytes(requirements.as_bytes()),
+            uv_version: uv_version.to_string(),
+            python_version: python_version.to_string(),
+            records: hash_records(env_path)?,
+        })
+    }
+}
+
+pub fn read_environment_stamp(env_path: &Path) -> Option<EnvironmentStamp> {
+    let contents = fs::read(env_path.join(ENVIRONMENT_STAMP)).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+pub fn write_environment_stamp(env_path: &Path, stamp: &EnvironmentStamp) -> Result<(), Error> {
+    fs::write(
+        env_path.join(ENVIRONMENT_STAMP),
+        serde_json::to_string_pretty(stamp)?,
+    )?;
+    Ok(())
+}
+
+pub fn remove_environment_stamp(env_path: &Path) -> Result<(), Error> {
+    match fs::remove_file(env_path.join(ENVIRONMENT_STAMP)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+fn hash_bytes(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Hashes the `RECORD` files of every installed distribution, which changes
+/// whenever a package is installed, removed or modified by hand.
+fn hash_records(env_path: &Path) -> Result<String, Error> {
+    let mut records = Vec::new();
+    let walker = walkdir::WalkDir::new(env_path)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() != 1 || entry.file_name() != ENVIRONMENTS_DIR);
+    for entry in walker {
+        let entry = entry?;
+        let is_record = entry.file_name() == "RECORD"
+            && entry
+                .path()
+                .parent()
+                .and_then(|parent| parent.extension())
+                .map_or(false, |ext| ext == "dist-info");
+        if is_record {
+            records.push(entry.path().to_path_buf());
+        }
+    }
+    records.sort();
+
+    let mut hasher = Sha256::new();
+    for record in records {
+        hasher.update(record.strip_prefix(env_path)?.to_string_lossy().as_bytes());
+        hasher.update(fs::read(&record)?);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -12,3 +12,9 @@ pub fn check_checksum(content: &[u8], checksum: &str) -> Result<(), Error> {
     }
     Ok(())
 }
+
+pub fn compute_checksum(content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content);
+    hex::encode(hasher.finalize())
+}
@@ -0,0 +1,117 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure, Error};
+use url::Url;
+
+use crate::uv::normalize_package_name;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistributionMetadata {
+    pub name: String,
+    pub version: String,
+}
+
+/// Reads the core metadata of a wheel (`.whl`) or source distribution (`.tar.gz`)
+/// and checks that it matches the file name.
+pub fn read_distribution_metadata(path: &Path) -> Result<DistributionMetadata, Error> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid distribution path {}", path.display()))?;
+    let contents = std::fs::read(path)?;
+    let (stem, metadata) = if let Some(stem) = file_name.strip_suffix(".whl") {
+        (stem, read_wheel_metadata(&contents)?)
+    } else if let Some(stem) = file_name.strip_suffix(".tar.gz") {
+        (stem, read_sdist_metadata(&contents)?)
+    } else {
+        bail!("{} is neither a wheel nor a source distribution", file_name);
+    };
+    let metadata = parse_metadata(&metadata)?;
+
+    // Both formats start with `{name}-{version}`, wheels append their tags.
+    let expected = normalize_package_name(&format!("{}-{}", metadata.name, metadata.version));
+    let actual = normalize_package_name(stem);
+    ensure!(
+        actual == expected || actual.starts_with(&format!("{}-", expected)),
+        "{} does not match its metadata ({} {})",
+        file_name,
+        metadata.name,
+        metadata.version
+    );
+    Ok(metadata)
+}
+
+/// Returns the requirement that installs the distribution at `path` as a
+/// `name @ file:///...` direct reference, which keeps paths with spaces intact.
+pub fn distribution_requirement(name: &str, path: &Path) -> Result<String, Error> {
+    let url = Url::from_file_path(path)
+        .map_err(|_| anyhow!("{} is not an absolute path", path.display()))?;
+    Ok(format!("{} @ {}", name, url))
+}
+
+fn read_wheel_metadata(contents: &[u8]) -> Result<String, Error> {
+    let mut archive = zip::read::ZipArchive::new(Cursor::new(contents))?;
+    let name = archive
+        .file_names()
+        .find(|name| {
+            let mut components = name.split('/');
+            matches!(
+                (components.next(), components.next(), components.next()),
+                (Some(dir), Some("METADATA"), None) if dir.ends_with(".dist-info")
+            )
+        })
+        .ok_or_else(|| anyhow!("Wheel does not contain a METADATA file"))?
+        .to_string();
+    let mut metadata = String::new();
+    archive.by_name(&name)?.read_to_string(&mut metadata)?;
+    Ok(metadata)
+}
+
+fn read_sdist_metadata(contents: &[u8]) -> Result<String, Error> {
+    let mut archive = tar::Archive::new(flate2::bufread::GzDecoder::new(contents));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        if path.components().count() == 2 && path.ends_with("PKG-INFO") {
+            let mut metadata = String::new();
+            entry.read_to_string(&mut metadata)?;
+            return Ok(metadata);
+        }
+    }
+    bail!("Source distribution does not contain a PKG-INFO file")
+}
+
+fn parse_metadata(metadata: &str) -> Result<DistributionMetadata, Error> {
+    let mut name = None;
+    let mut version = None;
+    // Headers end at the first empty line, the description follows.
+    for line in metadata.lines().take_while(|line| !line.trim().is_empty()) {
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Version:") {
+            version = Some(value.trim().to_string());
+        }
+    }
+    let name = name.ok_or_else(|| anyhow!("Metadata is missing the Name field"))?;
+    let version = version.ok_or_else(|| anyhow!("Metadata is missing the Version field"))?;
+    pep440_rs::Version::from_str(&version)
+        .map_err(|err| anyhow!("Invalid version {}: {}", version, err))?;
+    Ok(DistributionMetadata { name, version })
+}
+
+#[test]
+fn test_distribution_requirement() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir
+        .path()
+        .join("my plugins")
+        .join("omu_plugin_example-0.1.0-py3-none-any.whl");
+    let requirement = distribution_requirement("omu_plugin_example", &path).unwrap();
+    let (name, url) = requirement.split_once(" @ ").unwrap();
+    assert_eq!(name, "omu_plugin_example");
+    assert!(url.starts_with("file:///") && !url.contains(' '));
+    assert_eq!(Url::parse(url).unwrap().to_file_path().unwrap(), path);
+    assert!(distribution_requirement("omu_plugin_example", Path::new("relative.whl")).is_err());
+}
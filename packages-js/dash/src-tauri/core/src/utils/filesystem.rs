@@ -32,3 +32,19 @@ where
     }
     Ok(())
 }
+
+/// Total size in bytes of the files under `path`, zero if it does not exist.
+pub fn dir_size<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(0);
+    }
+    let mut size = 0;
+    for entry in walkdir::WalkDir::new(path) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
@@ -0,0 +1,5 @@
+pub mod archive;
+pub mod checksum;
+pub mod distribution;
+pub mod download;
+pub mod filesystem;
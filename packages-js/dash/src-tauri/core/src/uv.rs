@@ -0,0 +1,937 @@
+// https://github.com/astral-sh/rye/blob/main/rye/src/uv.rs - MIT licensed
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use log::debug;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+use crate::{
+    diagnostics::UvFailure,
+    environment::ProcessEnv,
+    index::{IndexConfig, PackageIndex},
+    options::AppOptions,
+    process::{system_runner, ProcessError, ProcessRunner},
+    sources::uv::{UvDownload, UvRequest},
+    utils::{
+        archive::unpack_archive,
+        download::download_url,
+        filesystem::{dir_size, remove_dir_all},
+    },
+};
+
+const VENV_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+const UNINSTALL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Installing downloads packages, which can take a while on slow connections.
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// Holds the index credentials, under the app directory.
+const NETRC_FILE: &str = "netrc";
+
+pub struct Uv {
+    uv_bin: PathBuf,
+    workdir: PathBuf,
+    python_bin: PathBuf,
+    pub index: PackageIndex,
+    pub version: String,
+    /// Cache directory passed as `UV_CACHE_DIR`, uv's global cache when unset.
+    cache_dir: Option<PathBuf>,
+    runner: Arc<dyn ProcessRunner>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum UvEnsureProgress {
+    Downloading {
+        msg: String,
+        progress: f64,
+        total: f64,
+    },
+    Extracting {
+        msg: String,
+        progress: f64,
+        total: f64,
+    },
+    UvCleanupOldVersions {
+        msg: String,
+        progress: f64,
+        total: f64,
+    },
+    UvUpdatePip {
+        msg: String,
+    },
+    UpdateRequirements {
+        msg: String,
+    },
+    SnapshotRequirements {
+        msg: String,
+    },
+    RestoreRequirements {
+        msg: String,
+    },
+    CreateEnvironment {
+        msg: String,
+    },
+    InstallPackages {
+        msg: String,
+    },
+    UninstallPackages {
+        msg: String,
+    },
+    PruneCache {
+        msg: String,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum UvEnsureError {
+    CleanupOldVersionsFailed { msg: String },
+    UpdatePipFailed { msg: String, failure: UvFailure },
+    UpdateRequirementsFailed { msg: String, failure: UvFailure },
+    SnapshotFailed { msg: String, failure: UvFailure },
+    RestoreFailed { msg: String, failure: UvFailure },
+    CreateEnvironmentFailed { msg: String, failure: UvFailure },
+    NoDownloadFound { msg: String },
+    InvalidIndex { msg: String },
+    ListPackagesFailed { msg: String, failure: UvFailure },
+    InstallPackagesFailed { msg: String, failure: UvFailure },
+    UninstallPackagesFailed { msg: String, failure: UvFailure },
+    CacheFailed { msg: String, failure: UvFailure },
+    TimedOut { msg: String },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UvCacheInfo {
+    pub path: PathBuf,
+    pub size: u64,
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+}
+
+impl Uv {
+    pub fn ensure(
+        options: &AppOptions,
+        python_bin: &PathBuf,
+        index: &IndexConfig,
+        cache_dir: Option<&Path>,
+        on_progress: impl Fn(UvEnsureProgress) + Send + Clone + 'static,
+    ) -> Result<Self, UvEnsureError> {
+        let mut index = index
+            .resolve()
+            .map_err(|msg| UvEnsureError::InvalidIndex { msg })?;
+        let netrc = options.appdir.join(NETRC_FILE);
+        index
+            .write_netrc(&netrc)
+            .map_err(|err| UvEnsureError::InvalidIndex {
+                msg: format!(
+                    "Failed to write index credentials to {}: {}",
+                    netrc.display(),
+                    err
+                ),
+            })?;
+        let download = UvDownload::try_from(UvRequest::default()).map_err(|err| {
+            UvEnsureError::NoDownloadFound {
+                msg: format!("No uv download found: {}", err),
+            }
+        })?;
+        let uv_dir = options.uv_path.join(download.version());
+        let uv_bin = if cfg!(target_os = "windows") {
+            let mut bin = uv_dir.join("uv");
+            bin.set_extension("exe");
+            bin
+        } else {
+            uv_dir.join("uv")
+        };
+        if uv_dir.exists() && uv_bin.exists() {
+            return Ok(Uv {
+                uv_bin,
+                workdir: options.workdir.clone(),
+                python_bin: python_bin.clone(),
+                index,
+                version: download.version(),
+                cache_dir: cache_dir.map(Path::to_path_buf),
+                runner: system_runner(),
+            });
+        }
+
+        Self::download(options, &on_progress)?;
+        Self::cleanup_old_versions(&options.uv_path, &uv_dir, &on_progress)?;
+        if uv_dir.exists() && uv_bin.exists() {
+            return Ok(Uv {
+                uv_bin,
+                workdir: options.workdir.clone(),
+                python_bin: python_bin.clone(),
+                index,
+                version: download.version(),
+                cache_dir: cache_dir.map(Path::to_path_buf),
+                runner: system_runner(),
+            });
+        }
+        Err(UvEnsureError::NoDownloadFound {
+            msg: format!("uv not found after installation at {}", uv_dir.display()),
+        })
+    }
+    fn cleanup_old_versions(
+        base_dir: &Path,
+        current_version: &Path,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        let versions = base_dir
+            .read_dir()
+            .map_err(|err| UvEnsureError::CleanupOldVersionsFailed {
+                msg: format!(
+                    "Failed to read uv versions directory {}: {}",
+                    base_dir.display(),
+                    err
+                ),
+            })?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter(|entry| entry.path() != current_version);
+
+        for entry in versions {
+            on_progress(UvEnsureProgress::UvCleanupOldVersions {
+                msg: format!("Removing old uv version: {}", entry.path().display()),
+                progress: 0.0,
+                total: 0.0,
+            });
+            remove_dir_all(&entry.path(), |progress, total| {
+                on_progress(UvEnsureProgress::UvCleanupOldVersions {
+                    msg: format!("Removing old uv version: {}", entry.path().display()),
+                    progress,
+                    total,
+                });
+            })
+            .map_err(|err| UvEnsureError::CleanupOldVersionsFailed {
+                msg: format!(
+                    "Failed to remove old uv version {}: {}",
+                    entry.path().display(),
+                    err
+                ),
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn download(
+        options: &AppOptions,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        let uv_request = UvRequest::default();
+        let uv_download = UvDownload::try_from(uv_request).unwrap();
+        let uv_url = uv_download.url.as_ref();
+        on_progress(UvEnsureProgress::Downloading {
+            msg: format!("Downloading uv from {}", uv_url),
+            progress: 0.0,
+            total: 0.0,
+        });
+        let contents = download_url(&uv_url, |progress, total| {
+            on_progress(UvEnsureProgress::Downloading {
+                msg: format!("Downloading uv from {}", uv_url),
+                progress,
+                total,
+            });
+        })
+        .map_err(|err| UvEnsureError::NoDownloadFound {
+            msg: format!("Failed to download uv from {}: {}", uv_url, err),
+        })?;
+        on_progress(UvEnsureProgress::Extracting {
+            msg: format!("Extracting uv to {}", options.uv_path.display()),
+            progress: 0.0,
+            total: 0.0,
+        });
+        let dst = options.uv_path.join(uv_download.version());
+        let strip = if cfg!(target_os = "windows") { 0 } else { 1 };
+        unpack_archive(&contents, &dst, strip, |progress, total| {
+            on_progress(UvEnsureProgress::Extracting {
+                msg: format!("Extracting uv to {}", options.uv_path.display()),
+                progress,
+                total,
+            });
+        })
+        .map_err(|err| UvEnsureError::NoDownloadFound {
+            msg: format!(
+                "Failed to extract uv to {}: {}",
+                options.uv_path.display(),
+                err
+            ),
+        })?;
+        Ok(())
+    }
+
+    pub fn cmd(&self) -> Command {
+        let mut cmd = Command::new(&self.uv_bin);
+        ProcessEnv::default().apply(&mut cmd);
+        cmd.current_dir(&self.workdir);
+        cmd.env("PROJECT_ROOT", make_project_root_fragment(&self.workdir));
+        if let Some(cache_dir) = &self.cache_dir {
+            cmd.env("UV_CACHE_DIR", cache_dir);
+        }
+        cmd.envs(self.index.credential_envs());
+
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            // 0x08000000: CREATE_NO_WINDOW https://learn.microsoft.com/ja-jp/windows/win32/procthread/process-creation-flags?redirectedfrom=MSDN#create_no_window
+            cmd.creation_flags(0x08000000);
+        }
+
+        cmd
+    }
+
+    /// Runs a uv command, giving up once `timeout` expires.
+    ///
+    /// Failing to start uv at all is left to the caller to report.
+    fn run(
+        &self,
+        cmd: &mut Command,
+        timeout: Duration,
+    ) -> Result<std::io::Result<Output>, UvEnsureError> {
+        match self.runner.run(cmd, timeout) {
+            Ok(output) => Ok(Ok(output)),
+            Err(ProcessError::Io(err)) => Ok(Err(err)),
+            Err(err) => Err(UvEnsureError::TimedOut {
+                msg: format!("{} {}", self.index.redact(&format!("{:?}", cmd)), err),
+            }),
+        }
+    }
+
+    /// Returns a uv that operates on the given interpreter instead.
+    pub fn with_python(&self, python_bin: &Path) -> Self {
+        Uv {
+            uv_bin: self.uv_bin.clone(),
+            workdir: self.workdir.clone(),
+            python_bin: python_bin.to_path_buf(),
+            index: self.index.clone(),
+            version: self.version.clone(),
+            cache_dir: self.cache_dir.clone(),
+            runner: self.runner.clone(),
+        }
+    }
+
+    /// Returns this uv with its processes run by `runner` instead.
+    #[cfg(test)]
+    pub fn with_runner(self, runner: Arc<dyn ProcessRunner>) -> Self {
+        Self { runner, ..self }
+    }
+
+    /// Creates a new virtual environment at `path` based on the current interpreter.
+    pub fn create_venv(
+        &self,
+        path: &Path,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        on_progress(UvEnsureProgress::CreateEnvironment {
+            msg: format!("Creating environment at {}", path.display()),
+        });
+        let mut cmd = self.cmd();
+        cmd.arg("venv")
+            .arg(path)
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin));
+        let output = self.run(&mut cmd, VENV_TIMEOUT)?.map_err(|err| {
+            UvEnsureError::CreateEnvironmentFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to create environment at {}: {}",
+                    path.display(),
+                    err
+                ),
+            }
+        })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::CreateEnvironmentFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to create environment at {}: {}",
+                    path.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Updates the venv to the given pip version and requirements.
+    ///
+    /// With `reinstall` every requirement is reinstalled even if already satisfied.
+    pub fn update(
+        &self,
+        pip_version: &str,
+        requirements: &str,
+        reinstall: bool,
+        on_progress: impl Fn(UvEnsureProgress) + Send + Clone + 'static,
+    ) -> Result<(), UvEnsureError> {
+        self.update_pip(pip_version, &on_progress)?;
+        self.update_requirements(requirements, reinstall, &on_progress)?;
+        Ok(())
+    }
+
+    /// Updates the pip version in the venv.
+    pub fn update_pip(
+        &self,
+        pip_version: &str,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        on_progress(UvEnsureProgress::UvUpdatePip {
+            msg: format!(
+                "Updating pip to {} at {}",
+                pip_version,
+                self.workdir.display()
+            ),
+        });
+        let mut cmd = self.cmd();
+        cmd.arg("pip")
+            .arg("install")
+            .arg(pip_version)
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin))
+            .args(self.index.uv_args());
+        let output =
+            self.run(&mut cmd, INSTALL_TIMEOUT)?
+                .map_err(|err| UvEnsureError::UpdatePipFailed {
+                    failure: UvFailure::from_io_error(&err),
+                    msg: format!(
+                        "unable to update pip to {} at {}: {}",
+                        pip_version,
+                        self.workdir.display(),
+                        err
+                    ),
+                })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            let update_error_message = format!(
+                "Failed to update pip to {} at {}: {}",
+                pip_version,
+                self.workdir.display(),
+                stderr
+            );
+            on_progress(UvEnsureProgress::UvUpdatePip {
+                msg: update_error_message.clone(),
+            });
+            return Err(UvEnsureError::UpdatePipFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: update_error_message.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Updates the requirements in the venv.
+    pub fn update_requirements(
+        &self,
+        requirements: &str,
+        reinstall: bool,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        debug!(
+            "Package index: {}",
+            self.index.redact(&self.index.uv_args().join(" "))
+        );
+        on_progress(UvEnsureProgress::UpdateRequirements {
+            msg: format!(
+                "Updating requirements {} at {}",
+                requirements,
+                self.python_bin.display()
+            ),
+        });
+
+        let mut req_file =
+            NamedTempFile::new().map_err(|err| UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to create temporary requirements file at {}: {}",
+                    self.workdir.display(),
+                    err
+                ),
+            })?;
+        writeln!(req_file, "{}", requirements).map_err(|err| {
+            UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to write to temporary requirements file at {}: {}",
+                    self.workdir.display(),
+                    err
+                ),
+            }
+        })?;
+
+        let mut cmd = self.cmd();
+        cmd.arg("pip")
+            .arg("install")
+            .arg("-r")
+            .arg(req_file.path())
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin))
+            .args(self.index.uv_args());
+        if cfg!(dev) {
+            cmd.arg("--no-cache");
+        }
+        if reinstall {
+            cmd.arg("--reinstall");
+        }
+        let output = self.run(&mut cmd, INSTALL_TIMEOUT)?.map_err(|err| {
+            UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to update requirements at {}: {}",
+                    self.workdir.display(),
+                    err
+                ),
+            }
+        })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to update requirements at {}: {}",
+                    self.workdir.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn uninstall_requirements(
+        &self,
+        requirements: &str,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        debug!(
+            "Package index: {}",
+            self.index.redact(&self.index.uv_args().join(" "))
+        );
+        on_progress(UvEnsureProgress::UpdateRequirements {
+            msg: format!(
+                "Uninstalling requirements {} at {}",
+                requirements,
+                self.python_bin.display()
+            ),
+        });
+
+        let mut req_file =
+            NamedTempFile::new().map_err(|err| UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to create temporary requirements file at {}: {}",
+                    self.workdir.display(),
+                    err
+                ),
+            })?;
+        writeln!(req_file, "{}", requirements).map_err(|err| {
+            UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to write to temporary requirements file at {}: {}",
+                    self.workdir.display(),
+                    err
+                ),
+            }
+        })?;
+
+        let mut cmd = self.cmd();
+        cmd.arg("pip")
+            .arg("uninstall")
+            .arg("-r")
+            .arg(req_file.path())
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin));
+        let output = self.run(&mut cmd, UNINSTALL_TIMEOUT)?.map_err(|err| {
+            UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to update requirements at {}: {}",
+                    self.workdir.display(),
+                    err
+                ),
+            }
+        })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::UpdateRequirementsFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to update requirements at {}: {}",
+                    self.workdir.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Captures the packages currently installed in the venv as a requirements list.
+    pub fn freeze(
+        &self,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<String, UvEnsureError> {
+        on_progress(UvEnsureProgress::SnapshotRequirements {
+            msg: format!(
+                "Taking snapshot of requirements at {}",
+                self.python_bin.display()
+            ),
+        });
+        let mut cmd = self.cmd();
+        cmd.arg("pip")
+            .arg("freeze")
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin));
+        let output =
+            self.run(&mut cmd, QUERY_TIMEOUT)?
+                .map_err(|err| UvEnsureError::SnapshotFailed {
+                    failure: UvFailure::from_io_error(&err),
+                    msg: format!(
+                        "unable to snapshot requirements at {}: {}",
+                        self.python_bin.display(),
+                        err
+                    ),
+                })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::SnapshotFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to snapshot requirements at {}: {}",
+                    self.python_bin.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Restores the venv to exactly the packages of a snapshot taken by [`Uv::freeze`].
+    pub fn restore(
+        &self,
+        snapshot: &str,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        on_progress(UvEnsureProgress::RestoreRequirements {
+            msg: format!(
+                "Restoring previous requirements at {}",
+                self.python_bin.display()
+            ),
+        });
+
+        let mut req_file = NamedTempFile::new().map_err(|err| UvEnsureError::RestoreFailed {
+            failure: UvFailure::from_io_error(&err),
+            msg: format!(
+                "unable to create temporary requirements file at {}: {}",
+                self.workdir.display(),
+                err
+            ),
+        })?;
+        writeln!(req_file, "{}", snapshot).map_err(|err| UvEnsureError::RestoreFailed {
+            failure: UvFailure::from_io_error(&err),
+            msg: format!(
+                "unable to write to temporary requirements file at {}: {}",
+                self.workdir.display(),
+                err
+            ),
+        })?;
+
+        let mut cmd = self.cmd();
+        cmd.arg("pip")
+            .arg("sync")
+            .arg(req_file.path())
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin))
+            .args(self.index.uv_args());
+        let output =
+            self.run(&mut cmd, INSTALL_TIMEOUT)?
+                .map_err(|err| UvEnsureError::RestoreFailed {
+                    failure: UvFailure::from_io_error(&err),
+                    msg: format!(
+                        "unable to restore requirements at {}: {}",
+                        self.workdir.display(),
+                        err
+                    ),
+                })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::RestoreFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to restore requirements at {}: {}",
+                    self.workdir.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Lists the packages installed in the venv.
+    pub fn list_packages(&self) -> Result<Vec<InstalledPackage>, UvEnsureError> {
+        let mut cmd = self.cmd();
+        cmd.arg("pip")
+            .arg("list")
+            .arg("--format")
+            .arg("json")
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin));
+        let output = self.run(&mut cmd, QUERY_TIMEOUT)?.map_err(|err| {
+            UvEnsureError::ListPackagesFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to list packages at {}: {}",
+                    self.python_bin.display(),
+                    err
+                ),
+            }
+        })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::ListPackagesFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to list packages at {}: {}",
+                    self.python_bin.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        serde_json::from_slice(&output.stdout).map_err(|err| UvEnsureError::ListPackagesFailed {
+            failure: UvFailure::Unknown,
+            msg: format!(
+                "Failed to parse package list at {}: {}",
+                self.python_bin.display(),
+                err
+            ),
+        })
+    }
+
+    /// Installs the given requirements into the venv, upgrading them if `upgrade` is set.
+    pub fn install_packages(
+        &self,
+        requirements: &[String],
+        upgrade: bool,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        on_progress(UvEnsureProgress::InstallPackages {
+            msg: format!(
+                "Installing {} at {}",
+                requirements.join(", "),
+                self.python_bin.display()
+            ),
+        });
+        let mut cmd = self.cmd();
+        cmd.arg("pip").arg("install");
+        if upgrade {
+            cmd.arg("--upgrade");
+        }
+        cmd.args(requirements)
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin))
+            .args(self.index.uv_args());
+        let output = self.run(&mut cmd, INSTALL_TIMEOUT)?.map_err(|err| {
+            UvEnsureError::InstallPackagesFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to install {} at {}: {}",
+                    requirements.join(", "),
+                    self.python_bin.display(),
+                    err
+                ),
+            }
+        })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::InstallPackagesFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to install {} at {}: {}",
+                    requirements.join(", "),
+                    self.python_bin.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Uninstalls the given packages from the venv.
+    pub fn uninstall_packages(
+        &self,
+        names: &[String],
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        on_progress(UvEnsureProgress::UninstallPackages {
+            msg: format!(
+                "Uninstalling {} at {}",
+                names.join(", "),
+                self.python_bin.display()
+            ),
+        });
+        let mut cmd = self.cmd();
+        cmd.arg("pip")
+            .arg("uninstall")
+            .args(names)
+            .arg("--python")
+            .arg(make_project_root_fragment(&self.python_bin));
+        let output = self.run(&mut cmd, UNINSTALL_TIMEOUT)?.map_err(|err| {
+            UvEnsureError::UninstallPackagesFailed {
+                failure: UvFailure::from_io_error(&err),
+                msg: format!(
+                    "unable to uninstall {} at {}: {}",
+                    names.join(", "),
+                    self.python_bin.display(),
+                    err
+                ),
+            }
+        })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::UninstallPackagesFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!(
+                    "Failed to uninstall {} at {}: {}",
+                    names.join(", "),
+                    self.python_bin.display(),
+                    stderr
+                ),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// The cache directory this uv uses, if it is managed by the dashboard.
+    pub fn managed_cache_dir(&self) -> Option<&Path> {
+        self.cache_dir.as_deref()
+    }
+
+    /// Reports where the cache is and how much space it takes.
+    pub fn cache_info(&self) -> Result<UvCacheInfo, UvEnsureError> {
+        let path = match &self.cache_dir {
+            Some(path) => path.clone(),
+            None => {
+                let mut cmd = self.cmd();
+                cmd.arg("cache").arg("dir");
+                let output = self.run(&mut cmd, QUERY_TIMEOUT)?.map_err(|err| {
+                    UvEnsureError::CacheFailed {
+                        failure: UvFailure::from_io_error(&err),
+                        msg: format!("unable to locate uv cache: {}", err),
+                    }
+                })?;
+                if !output.status.success() {
+                    let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+                    Err(UvEnsureError::CacheFailed {
+                        failure: UvFailure::classify(&stderr),
+                        msg: format!("Failed to locate uv cache: {}", stderr),
+                    })?;
+                }
+                PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+            }
+        };
+        let size = dir_size(&path).map_err(|err| UvEnsureError::CacheFailed {
+            failure: UvFailure::Unknown,
+            msg: format!(
+                "Failed to compute size of uv cache at {}: {}",
+                path.display(),
+                err
+            ),
+        })?;
+        Ok(UvCacheInfo {
+            path,
+            size,
+            shared: self.cache_dir.is_none(),
+        })
+    }
+
+    /// Removes unused entries from the cache.
+    pub fn prune_cache(
+        &self,
+        on_progress: &(impl Fn(UvEnsureProgress) + Send + 'static),
+    ) -> Result<(), UvEnsureError> {
+        on_progress(UvEnsureProgress::PruneCache {
+            msg: "Pruning uv cache".to_string(),
+        });
+        let mut cmd = self.cmd();
+        cmd.arg("cache").arg("prune");
+        let output =
+            self.run(&mut cmd, UNINSTALL_TIMEOUT)?
+                .map_err(|err| UvEnsureError::CacheFailed {
+                    failure: UvFailure::from_io_error(&err),
+                    msg: format!("unable to prune uv cache: {}", err),
+                })?;
+
+        if !output.status.success() {
+            let stderr = self.index.redact(&String::from_utf8_lossy(&output.stderr));
+            Err(UvEnsureError::CacheFailed {
+                failure: UvFailure::classify(&stderr),
+                msg: format!("Failed to prune uv cache: {}", stderr),
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn make_project_root_fragment(root: &Path) -> String {
+    // XXX: ${PROJECT_ROOT} is supposed to be used in the context of file:///
+    // so let's make sure it is url escaped.  This is pretty hacky but
+    // good enough for now.
+    // No leading slash to fit with file:///${PROJECT_ROOT} convention
+    root.to_string_lossy()
+        .trim_start_matches('/')
+        .replace(' ', "%20")
+}
+
+/// Normalizes a project name as described in PEP 503.
+pub fn normalize_package_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+/// Returns the normalized project name of a requirement such as `omu_chat>=0.1`.
+pub fn requirement_name(requirement: &str) -> Option<String> {
+    let requirement = requirement.trim();
+    let end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    let name = &requirement[..end];
+    if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some(normalize_package_name(name))
+}
@@ -0,0 +1,398 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use omuapps_core::{
+    logs::LogFilter,
+    process::new_process_group,
+    server::{running_version, ServerAddress, ServerStats},
+};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, Url, WebviewUrl};
+use tauri_plugin_cli::{ArgData, CliExt, Matches};
+
+use crate::{
+    commands::{clean, start, stop, StartResult, StartServerOptions},
+    AppState,
+};
+
+/// Subcommands answered by the dashboard, see `plugins.cli` in `tauri.conf.json`.
+const SUBCOMMANDS: &[&str] = &[
+    "start", "stop", "restart", "status", "clean", "logs", "open",
+];
+/// Added when relaunching ourselves, tells the dashboard where to send the answer.
+const REPLY_ARG: &str = "--reply-port";
+/// How long the dashboard has to pick up a forwarded subcommand.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+enum Reply {
+    Stdout { line: String },
+    Stderr { line: String },
+    Exit { code: i32 },
+}
+
+#[derive(serde::Serialize)]
+struct Status {
+    /// Whether an omuserver answers on the configured port.
+    running: bool,
+    /// Whether this dashboard supervises it.
+    owned: bool,
+    version: Option<String>,
+    address: ServerAddress,
+    stats: Option<ServerStats>,
+}
+
+/// Runs a subcommand given to this process in the dashboard and returns the
+/// exit code, or `None` to start the dashboard as usual.
+///
+/// The single-instance plugin forwards only the arguments of a second
+/// instance and then exits it, so we relaunch ourselves with a port to
+/// answer on and wait for the running dashboard, or the relaunched one if
+/// none is running, to connect back.
+///
+/// On Windows, cmd.exe does not wait for the dashboard, which is a GUI
+/// program, so the answer is printed after its prompt. Run it with
+/// `start /wait` to get the exit code.
+pub fn forward(args: &[String]) -> Option<i32> {
+    let subcommand = args.iter().skip(1).find(|arg| !arg.starts_with('-'))?;
+    if !SUBCOMMANDS.contains(&subcommand.as_str()) || args.iter().any(|arg| arg == REPLY_ARG) {
+        return None;
+    }
+    attach_console();
+    match relaunch(args) {
+        Ok(code) => Some(code),
+        Err(err) => {
+            eprintln!("Failed to reach the dashboard: {}", err);
+            Some(1)
+        }
+    }
+}
+
+/// Release builds use the GUI subsystem on Windows and start without a
+/// console, so the answer would be lost unless we use the terminal's.
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // SAFETY: AttachConsole takes no pointers, and fails harmlessly when we
+    // already have a console or the parent has none.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+fn relaunch(args: &[String]) -> std::io::Result<i32> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    listener.set_nonblocking(true)?;
+    let port = listener.local_addr()?.port();
+
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.args(&args[1..])
+        .arg(REPLY_ARG)
+        .arg(port.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // A dashboard started for `start` must outlive this terminal.
+    new_process_group(&mut cmd);
+    let mut child = cmd.spawn()?;
+
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    let stream = loop {
+        match listener.accept() {
+            Ok((stream, _)) => break stream,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err),
+        }
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("dashboard exited with {}", status),
+                ));
+            }
+        }
+        if Instant::now() >= deadline {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "the dashboard did not answer",
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    stream.set_nonblocking(false)?;
+
+    for line in BufReader::new(stream).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(Reply::Stdout { line }) => println!("{}", line),
+            Ok(Reply::Stderr { line }) => eprintln!("{}", line),
+            Ok(Reply::Exit { code }) => return Ok(code),
+            Err(err) => warn!("Invalid reply from the dashboard: {}", err),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "the dashboard closed the connection without an exit code",
+    ))
+}
+
+/// Where the answer to a subcommand goes, the log if there is no one to send it to.
+struct Responder {
+    stream: Option<TcpStream>,
+}
+
+impl Responder {
+    fn connect(port: Option<u16>) -> Self {
+        let stream = port.and_then(|port| {
+            TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+                .map_err(|err| warn!("Failed to answer subcommand on port {}: {}", port, err))
+                .ok()
+        });
+        Self { stream }
+    }
+
+    /// Returns `false` once the invoking process went away.
+    fn send(&mut self, reply: Reply) -> bool {
+        let Some(stream) = self.stream.as_mut() else {
+            match reply {
+                Reply::Stdout { line } => info!("{}", line),
+                Reply::Stderr { line } => warn!("{}", line),
+                Reply::Exit { code } => info!("Subcommand exited with {}", code),
+            }
+            return true;
+        };
+        let mut json = serde_json::to_string(&reply).unwrap();
+        json.push('\n');
+        stream.write_all(json.as_bytes()).is_ok()
+    }
+
+    /// Whether the invoking process is still there, which only shows once it
+    /// closed the connection since it never writes to it.
+    fn connected(&self) -> bool {
+        let Some(stream) = self.stream.as_ref() else {
+            return false;
+        };
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let connected = match stream.peek(&mut [0]) {
+            Ok(read) => read > 0,
+            Err(err) => err.kind() == std::io::ErrorKind::WouldBlock,
+        };
+        connected && stream.set_nonblocking(false).is_ok()
+    }
+
+    fn out(&mut self, line: impl Into<String>) -> bool {
+        self.send(Reply::Stdout { line: line.into() })
+    }
+}
+
+/// Handles the subcommand in `argv`, returning `false` if there is none.
+///
+/// `launched` is set when this dashboard was started only to answer it, in
+/// which case it exits afterwards unless the subcommand leaves it running.
+pub fn handle(app: &AppHandle, argv: Vec<String>, launched: bool) -> bool {
+    let port = reply_port(&argv);
+    let matches = match app.cli().matches_from(argv) {
+        Ok(matches) => matches,
+        Err(err) if port.is_some() => {
+            // Usage errors of a forwarded subcommand still need an answer.
+            let mut responder = Responder::connect(port);
+            responder.send(Reply::Stderr {
+                line: err.to_string(),
+            });
+            responder.send(Reply::Exit { code: 2 });
+            if launched {
+                app.exit(2);
+            }
+            return true;
+        }
+        Err(err) => {
+            warn!("Failed to parse arguments: {}", err);
+            return false;
+        }
+    };
+    let Some(subcommand) = matches.subcommand else {
+        return false;
+    };
+    let name = subcommand.name;
+    let args = subcommand.matches;
+    info!("Running subcommand {}", name);
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut responder = Responder::connect(port);
+        let code = run(&app, &name, &args, &mut responder);
+        responder.send(Reply::Exit { code });
+        if launched && !matches!(name.as_str(), "start" | "restart" | "open") {
+            app.exit(code);
+        }
+    });
+    true
+}
+
+/// The port appended by [`forward`], read without the parser so that usage
+/// errors can be answered as well.
+fn reply_port(argv: &[String]) -> Option<u16> {
+    let index = argv.iter().position(|arg| arg == REPLY_ARG)?;
+    argv.get(index + 1)?.parse().ok()
+}
+
+fn run(app: &AppHandle, name: &str, args: &Matches, responder: &mut Responder) -> i32 {
+    let state = app.state::<AppState>();
+    let result = match name {
+        "start" => start_server(app, &state, responder),
+        "stop" => stop_server(app, &state, responder),
+        "restart" => {
+            stop_server(app, &state, responder).and_then(|_| start_server(app, &state, responder))
+        }
+        "status" => status(&state, arg_flag(&args.args, "json"), responder),
+        "clean" => clean_environment(app, &state, responder),
+        "logs" => logs(&state, arg_flag(&args.args, "follow"), responder),
+        "open" => open(app, arg_str(&args.args, "url").unwrap_or_default()),
+        _ => Err(format!("Unknown subcommand {}", name)),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(msg) => {
+            responder.send(Reply::Stderr { line: msg });
+            1
+        }
+    }
+}
+
+fn stop_server(app: &AppHandle, state: &AppState, responder: &mut Responder) -> Result<(), String> {
+    stop(app, state).map_err(|err| format!("Failed to stop server: {:?}", err))?;
+    responder.out("Server stopped");
+    Ok(())
+}
+
+fn clean_environment(
+    app: &AppHandle,
+    state: &AppState,
+    responder: &mut Responder,
+) -> Result<(), String> {
+    clean(app, state).map_err(|err| format!("Failed to clean environment: {:?}", err))?;
+    responder.out("Environment cleaned");
+    Ok(())
+}
+
+fn start_server(
+    app: &AppHandle,
+    state: &AppState,
+    responder: &mut Responder,
+) -> Result<(), String> {
+    let options = StartServerOptions {
+        force_reinstall: None,
+        wait_ready: Some(true),
+    };
+    let port = || state.server_config.lock().unwrap().port;
+    let result =
+        start(app, state, options).map_err(|err| format!("Failed to start server: {:?}", err))?;
+    responder.out(match result {
+        StartResult::AlreadyRunning { .. } => {
+            format!("Server is already running on port {}", port())
+        }
+        _ => format!("Server is ready on port {}", port()),
+    });
+    Ok(())
+}
+
+fn status(state: &AppState, json: bool, responder: &mut Responder) -> Result<(), String> {
+    let address = state.server_config.lock().unwrap().address();
+    let server = state.server.lock().unwrap();
+    let owned = server.as_ref().map_or(false, |server| server.is_running());
+    let stats = server.as_ref().and_then(|server| server.stats());
+    drop(server);
+    let version = running_version(address.port);
+    let status = Status {
+        running: version.is_some(),
+        owned,
+        version,
+        address,
+        stats,
+    };
+    if json {
+        responder.out(serde_json::to_string(&status).unwrap());
+        return Ok(());
+    }
+    let running = match (&status.version, status.owned) {
+        (Some(version), true) => format!("running, version {}", version),
+        (Some(version), false) => {
+            format!("running, version {}, not started by the dashboard", version)
+        }
+        (None, _) => "not running".to_string(),
+    };
+    responder.out(format!(
+        "Server on {}:{}: {}",
+        status.address.host, status.address.port, running
+    ));
+    if let Some(stats) = &status.stats {
+        responder.out(format!(
+            "pid {}, cpu {:.1}%, memory {} MB, up {}s",
+            stats.pid,
+            stats.cpu_percent,
+            stats.rss_bytes / (1024 * 1024),
+            stats.uptime_secs
+        ));
+    }
+    Ok(())
+}
+
+fn logs(state: &AppState, follow: bool, responder: &mut Responder) -> Result<(), String> {
+    let mut filter = LogFilter::default();
+    loop {
+        for line in state.server_logs.query(&filter) {
+            filter.since = Some(line.seq + 1);
+            if !responder.out(line.line) {
+                return Ok(());
+            }
+        }
+        // Without output to notice a failed write on, check for the
+        // invoking process going away instead.
+        if !follow || !responder.connected() {
+            return Ok(());
+        }
+        std::thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+/// Opens `url` in a window of the dashboard.
+fn open(app: &AppHandle, url: &str) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|err| format!("Invalid URL {}: {}", url, err))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Only http and https URLs can be opened, not {}",
+            url
+        ));
+    }
+    let label = format!("cli-{}", chrono::Local::now().timestamp_millis());
+    let title = url.host_str().unwrap_or_default().to_string();
+    tauri::WebviewWindowBuilder::new(app, label, WebviewUrl::External(url))
+        .title(title)
+        .build()
+        .map_err(|err| format!("Failed to open window: {}", err))?;
+    Ok(())
+}
+
+fn arg_str<'a>(args: &'a HashMap<String, ArgData>, name: &str) -> Option<&'a str> {
+    args.get(name)?.value.as_str()
+}
+
+fn arg_flag(args: &HashMap<String, ArgData>, name: &str) -> bool {
+    args.get(name).map(|arg| &arg.value) == Some(&Value::Bool(true))
+}
@@ -1,4 +1,6 @@
-use crate::{AppConfig, AppState};
+use omuapps_core::options::AppConfig;
+
+use crate::AppState;
 
 #[tauri::command]
 pub fn get_config(state: tauri::State<'_, AppState>) -> Result<AppConfig, String> {
@@ -11,7 +13,7 @@ pub fn set_config(state: tauri::State<'_, AppState>, config: AppConfig) -> Resul
     let mut config_state = state.config.lock().unwrap();
     *config_state = config.clone();
     config
-        .store(&state.options.appdir)
+        .store(&AppConfig::path(&state.options))
         .map_err(|err| format!("Failed to store config: {}", err))?;
     Ok(())
 }
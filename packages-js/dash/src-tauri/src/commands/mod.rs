@@ -1,6 +1,8 @@
 pub mod config;
 pub mod dashboard;
+pub mod packages;
 pub mod server;
 pub(crate) use config::*;
 pub(crate) use dashboard::*;
+pub(crate) use packages::*;
 pub(crate) use server::*;
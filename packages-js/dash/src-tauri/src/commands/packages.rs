@@ -0,0 +1,371 @@
+use std::path::PathBuf;
+
+use log::{info, warn};
+use tauri::Emitter;
+
+use omuapps_core::{
+    python::{Python, PythonEnsureError, PythonEnsureProgress},
+    server::{is_protected_package, Server, SideloadedPlugin},
+    utils::{
+        checksum::compute_checksum,
+        distribution::{distribution_requirement, read_distribution_metadata},
+    },
+    uv::{requirement_name, Uv, UvCacheInfo, UvEnsureError, UvEnsureProgress},
+};
+
+use crate::AppState;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PackageInfo {
+    name: String,
+    version: String,
+    protected: bool,
+    /// The requirement the user added this package with, re-applied on every update.
+    requirement: Option<String>,
+    sideloaded: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum PackageError {
+    PythonEnsureError { reason: PythonEnsureError },
+    UvEnsureError { reason: UvEnsureError },
+    InvalidRequirement { msg: String },
+    ProtectedPackage { msg: String },
+    InvalidDistribution { msg: String },
+    SideloadFailed { msg: String },
+    ConfigStoreFailed { msg: String },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum PackageProgress {
+    Python { progress: PythonEnsureProgress },
+    Uv { progress: UvEnsureProgress },
+}
+
+fn ensure_uv(
+    state: &AppState,
+    on_progress: impl Fn(PackageProgress) + Send + Clone + 'static,
+) -> Result<Uv, PackageError> {
+    let callback = on_progress.clone();
+    let python = Python::ensure(&state.options, move |progress| {
+        callback(PackageProgress::Python { progress });
+    })
+    .map_err(|err| PackageError::PythonEnsureError { reason: err })?;
+    let python = Server::active_environment(&python);
+    let index = state.server_config.lock().unwrap().index.clone();
+    Uv::ensure(
+        &state.options,
+        &python.python_bin,
+        &index,
+        state.uv_cache_dir().as_deref(),
+        move |progress| {
+            on_progress(PackageProgress::Uv { progress });
+        },
+    )
+    .map_err(|err| PackageError::UvEnsureError { reason: err })
+}
+
+fn find_plugin<'a>(plugins: &'a [String], name: &str) -> Option<&'a String> {
+    plugins
+        .iter()
+        .find(|plugin| requirement_name(plugin).as_deref() == Some(name))
+}
+
+fn find_sideloaded<'a>(
+    sideloaded: &'a [SideloadedPlugin],
+    name: &str,
+) -> Option<&'a SideloadedPlugin> {
+    sideloaded
+        .iter()
+        .find(|plugin| requirement_name(&plugin.name).as_deref() == Some(name))
+}
+
+#[tauri::command]
+pub async fn list_packages(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PackageInfo>, PackageError> {
+    let on_progress = move |progress: PackageProgress| {
+        window.emit("package_progress", progress).unwrap();
+    };
+    let uv = ensure_uv(&state, on_progress)?;
+    let packages = uv
+        .list_packages()
+        .map_err(|err| PackageError::UvEnsureError { reason: err })?;
+    let config = state.server_config.lock().unwrap().clone();
+    Ok(packages
+        .into_iter()
+        .map(|package| {
+            let name = requirement_name(&package.name).unwrap_or_default();
+            PackageInfo {
+                protected: is_protected_package(&name),
+                requirement: find_plugin(&config.plugins, &name).cloned(),
+                sideloaded: find_sideloaded(&config.sideloaded, &name).is_some(),
+                name: package.name,
+                version: package.version,
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn install_package(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    requirement: String,
+) -> Result<(), PackageError> {
+    let on_progress = move |progress: PackageProgress| {
+        info!("{:?}", progress);
+        window.emit("package_progress", progress).unwrap();
+    };
+    let requirement = requirement.trim().to_string();
+    let name = requirement_name(&requirement).ok_or_else(|| PackageError::InvalidRequirement {
+        msg: format!("Invalid requirement: {}", requirement),
+    })?;
+    if is_protected_package(&name) {
+        return Err(PackageError::ProtectedPackage {
+            msg: format!("{} is managed by the dashboard", name),
+        });
+    }
+
+    let uv = ensure_uv(&state, on_progress.clone())?;
+    uv.install_packages(
+        std::slice::from_ref(&requirement),
+        false,
+        &move |progress| {
+            on_progress(PackageProgress::Uv { progress });
+        },
+    )
+    .map_err(|err| PackageError::UvEnsureError { reason: err })?;
+
+    state
+        .update_server_config(|config| {
+            config
+                .plugins
+                .retain(|plugin| requirement_name(plugin).as_deref() != Some(&name));
+            config.plugins.push(requirement);
+        })
+        .map_err(|err| PackageError::ConfigStoreFailed {
+            msg: format!("Failed to store server config: {}", err),
+        })
+}
+
+#[tauri::command]
+pub async fn upgrade_package(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> Result<(), PackageError> {
+    let on_progress = move |progress: PackageProgress| {
+        info!("{:?}", progress);
+        window.emit("package_progress", progress).unwrap();
+    };
+    let name = requirement_name(&name).ok_or_else(|| PackageError::InvalidRequirement {
+        msg: format!("Invalid package name: {}", name),
+    })?;
+    if is_protected_package(&name) {
+        return Err(PackageError::ProtectedPackage {
+            msg: format!("{} is upgraded together with the dashboard", name),
+        });
+    }
+    let requirement = {
+        let config = state.server_config.lock().unwrap();
+        find_plugin(&config.plugins, &name).cloned().unwrap_or(name)
+    };
+
+    let uv = ensure_uv(&state, on_progress.clone())?;
+    uv.install_packages(&[requirement], true, &move |progress| {
+        on_progress(PackageProgress::Uv { progress });
+    })
+    .map_err(|err| PackageError::UvEnsureError { reason: err })
+}
+
+#[tauri::command]
+pub async fn remove_package(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> Result<(), PackageError> {
+    let on_progress = move |progress: PackageProgress| {
+        info!("{:?}", progress);
+        window.emit("package_progress", progress).unwrap();
+    };
+    let name = requirement_name(&name).ok_or_else(|| PackageError::InvalidRequirement {
+        msg: format!("Invalid package name: {}", name),
+    })?;
+    if is_protected_package(&name) {
+        return Err(PackageError::ProtectedPackage {
+            msg: format!(
+                "{} is required by the dashboard and cannot be removed",
+                name
+            ),
+        });
+    }
+
+    let uv = ensure_uv(&state, on_progress.clone())?;
+    uv.uninstall_packages(std::slice::from_ref(&name), &move |progress| {
+        on_progress(PackageProgress::Uv { progress });
+    })
+    .map_err(|err| PackageError::UvEnsureError { reason: err })?;
+
+    state
+        .update_server_config(|config| {
+            config
+                .plugins
+                .retain(|plugin| requirement_name(plugin).as_deref() != Some(&name));
+            if let Some(plugin) = find_sideloaded(&config.sideloaded, &name) {
+                if let Err(err) = std::fs::remove_file(&plugin.path) {
+                    warn!("Failed to remove {}: {}", plugin.path.display(), err);
+                }
+            }
+            config
+                .sideloaded
+                .retain(|plugin| requirement_name(&plugin.name).as_deref() != Some(&name));
+        })
+        .map_err(|err| PackageError::ConfigStoreFailed {
+            msg: format!("Failed to store server config: {}", err),
+        })
+}
+
+/// Installs a plugin from a local wheel or source distribution.
+///
+/// The file is copied into the server data directory so that later updates
+/// can install it again.
+#[tauri::command]
+pub async fn sideload_package(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<(), PackageError> {
+    let on_progress = move |progress: PackageProgress| {
+        info!("{:?}", progress);
+        window.emit("package_progress", progress).unwrap();
+    };
+    let source = PathBuf::from(&path);
+    let metadata =
+        read_distribution_metadata(&source).map_err(|err| PackageError::InvalidDistribution {
+            msg: format!("Invalid distribution {}: {}", path, err),
+        })?;
+    let name =
+        requirement_name(&metadata.name).ok_or_else(|| PackageError::InvalidDistribution {
+            msg: format!("Invalid package name: {}", metadata.name),
+        })?;
+    if is_protected_package(&name) {
+        return Err(PackageError::ProtectedPackage {
+            msg: format!("{} is managed by the dashboard", name),
+        });
+    }
+
+    let contents = std::fs::read(&source).map_err(|err| PackageError::SideloadFailed {
+        msg: format!("Failed to read {}: {}", path, err),
+    })?;
+    let sideload_dir = state.server_config.lock().unwrap().workdir.join("sideload");
+    std::fs::create_dir_all(&sideload_dir).map_err(|err| PackageError::SideloadFailed {
+        msg: format!(
+            "Failed to create sideload directory {}: {}",
+            sideload_dir.display(),
+            err
+        ),
+    })?;
+    // The copy is installed from a staging directory and only replaces an
+    // earlier copy of the same file once the install succeeded. It keeps its
+    // file name, which uv reads the version and tags of a wheel from.
+    let file_name = source.file_name().unwrap();
+    let staging_dir = sideload_dir.join(".staging");
+    let staged = staging_dir.join(file_name);
+    let destination = sideload_dir.join(file_name);
+    std::fs::create_dir_all(&staging_dir)
+        .and_then(|_| std::fs::write(&staged, &contents))
+        .map_err(|err| PackageError::SideloadFailed {
+            msg: format!("Failed to copy {} to {}: {}", path, staged.display(), err),
+        })?;
+
+    let installed = distribution_requirement(&metadata.name, &staged)
+        .map_err(|err| PackageError::SideloadFailed {
+            msg: format!("Failed to install {}: {}", staged.display(), err),
+        })
+        .and_then(|requirement| {
+            let uv = ensure_uv(&state, on_progress.clone())?;
+            uv.install_packages(&[requirement], true, &move |progress| {
+                on_progress(PackageProgress::Uv { progress });
+            })
+            .map_err(|err| PackageError::UvEnsureError { reason: err })
+        })
+        .and_then(|_| {
+            std::fs::rename(&staged, &destination).map_err(|err| PackageError::SideloadFailed {
+                msg: format!(
+                    "Failed to move {} to {}: {}",
+                    staged.display(),
+                    destination.display(),
+                    err
+                ),
+            })
+        });
+    if let Err(err) = installed {
+        if let Err(err) = std::fs::remove_file(&staged) {
+            warn!("Failed to remove {}: {}", staged.display(), err);
+        }
+        return Err(err);
+    }
+
+    let plugin = SideloadedPlugin {
+        name: metadata.name,
+        version: metadata.version,
+        path: destination,
+        sha256: compute_checksum(&contents),
+    };
+    state
+        .update_server_config(|config| {
+            if let Some(previous) = find_sideloaded(&config.sideloaded, &name) {
+                if previous.path != plugin.path {
+                    if let Err(err) = std::fs::remove_file(&previous.path) {
+                        warn!("Failed to remove {}: {}", previous.path.display(), err);
+                    }
+                }
+            }
+            config
+                .sideloaded
+                .retain(|plugin| requirement_name(&plugin.name).as_deref() != Some(&name));
+            config
+                .plugins
+                .retain(|plugin| requirement_name(plugin).as_deref() != Some(&name));
+            config.sideloaded.push(plugin);
+        })
+        .map_err(|err| PackageError::ConfigStoreFailed {
+            msg: format!("Failed to store server config: {}", err),
+        })
+}
+
+#[tauri::command]
+pub async fn get_uv_cache(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+) -> Result<UvCacheInfo, PackageError> {
+    let on_progress = move |progress: PackageProgress| {
+        window.emit("package_progress", progress).unwrap();
+    };
+    let uv = ensure_uv(&state, on_progress)?;
+    uv.cache_info()
+        .map_err(|err| PackageError::UvEnsureError { reason: err })
+}
+
+/// Removes unused entries from the uv cache and reports what is left.
+#[tauri::command]
+pub async fn prune_uv_cache(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+) -> Result<UvCacheInfo, PackageError> {
+    let on_progress = move |progress: PackageProgress| {
+        info!("{:?}", progress);
+        window.emit("package_progress", progress).unwrap();
+    };
+    let uv = ensure_uv(&state, on_progress.clone())?;
+    uv.prune_cache(&move |progress| {
+        on_progress(PackageProgress::Uv { progress });
+    })
+    .map_err(|err| PackageError::UvEnsureError { reason: err })?;
+    uv.cache_info()
+        .map_err(|err| PackageError::UvEnsureError { reason: err })
+}
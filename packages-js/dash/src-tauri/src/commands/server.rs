@@ -1,12 +1,16 @@
 use std::{fs::create_dir_all, path::PathBuf};
 
-use crate::{
+use omuapps_core::{
+    logs::{LogFilter, LogLine},
     python::{Python, PythonEnsureError, PythonEnsureProgress},
-    server::{Server, ServerEnsureError, ServerEnsureProgress},
+    server::{
+        LaunchOptions, ReadyState, Server, ServerAddress, ServerEnsureError, ServerStats,
+        StartError, StartProgress, READY_WAIT_TIMEOUT,
+    },
     utils::{archive::pack_archive, filesystem::remove_dir_all},
-    uv::{Uv, UvEnsureError, UvEnsureProgress},
-    AppState,
 };
+
+use crate::AppState;
 use log::info;
 use tauri::{Emitter, Manager};
 
@@ -17,40 +21,50 @@ pub struct Progress {
     total: f64,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
-#[serde(tag = "type")]
-pub enum StartError {
-    ServerStartFailed { msg: String },
-    PythonEnsureError { reason: PythonEnsureError },
-    UvEnsureError { reason: UvEnsureError },
-    ServerEnsureError { reason: ServerEnsureError },
-}
-
-#[derive(Debug, Clone, serde::Serialize)]
-#[serde(tag = "type")]
-pub enum StartProgress {
-    Python { progress: PythonEnsureProgress },
-    Uv { progress: UvEnsureProgress },
-    Server { progress: ServerEnsureProgress },
-}
-
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "type")]
 pub enum StartResult {
     AlreadyRunning { token: String },
     Starting { token: String },
+    Ready { token: String },
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct StartServerOptions {
+    pub force_reinstall: Option<bool>,
+    /// Resolve only once the server answers on its port.
+    pub wait_ready: Option<bool>,
 }
 
 #[tauri::command]
 pub async fn start_server(
-    window: tauri::Window,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
+    options: Option<StartServerOptions>,
 ) -> Result<StartResult, StartError> {
+    start(&app, &state, options.unwrap_or_default())
+}
+
+/// Provisions and starts the server, shared by [`start_server`] and the CLI.
+pub fn start(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    options: StartServerOptions,
+) -> Result<StartResult, StartError> {
+    let emitter = app.clone();
     let on_progress = move |progress: StartProgress| {
         info!("{:?}", progress);
-        window.emit("start_progress", progress).unwrap();
+        emitter.emit("start_progress", progress).unwrap();
     };
     info!("Starting server");
+    // Serializes starts without holding the server slot, which commands
+    // like `get_token` and the exit hook need while the server starts.
+    let _starting = state
+        .starting
+        .lock()
+        .map_err(|err| StartError::ServerStartFailed {
+            msg: format!("Failed to lock start mutex: {}", err),
+        })?;
     {
         let server_mutex = state
             .server
@@ -68,51 +82,83 @@ pub async fn start_server(
         };
     }
 
-    let config = state.config.lock().unwrap().clone();
-    let callback = on_progress.clone();
-    let python = Python::ensure(&state.options, move |progress: PythonEnsureProgress| {
-        callback(StartProgress::Python { progress: progress });
-    })
-    .map_err(|err| StartError::PythonEnsureError { reason: err })?;
-    let callback = on_progress.clone();
-    let uv = Uv::ensure(
+    let app_config = state.config.lock().unwrap().clone();
+    let server = Server::ensure_and_start(
         &state.options,
-        &python.python_bin,
-        move |progress: UvEnsureProgress| {
-            callback(StartProgress::Uv { progress });
-        },
-    )
-    .map_err(|err| StartError::UvEnsureError { reason: err })?;
-    let callback = on_progress.clone();
-    let server = Server::ensure_server(
+        &app_config,
         &state.server_config,
-        python,
-        uv,
-        move |progress: ServerEnsureProgress| {
-            callback(StartProgress::Server { progress });
-        },
-        state.app_handle.clone(),
-    )
-    .map_err(|err| StartError::ServerEnsureError { reason: err })?;
+        options.force_reinstall.unwrap_or(false),
+        on_progress,
+        state.events.clone(),
+        state.server_logs.clone(),
+    )?;
 
     let token = server.token.clone();
-    let mut server_mutex = state
+    let already_started = server.already_started;
+    let readiness = server.readiness();
+    *state.server.lock().unwrap() = Some(server);
+    if already_started {
+        return Ok(StartResult::AlreadyRunning { token });
+    }
+    if !options.wait_ready.unwrap_or(false) {
+        return Ok(StartResult::Starting { token });
+    }
+    let port = state.server_config.lock().unwrap().port;
+    match readiness.wait(READY_WAIT_TIMEOUT) {
+        ReadyState::Ready => Ok(StartResult::Ready { token }),
+        ReadyState::Exited => Err(StartError::ServerStartFailed {
+            msg: format!("Server exited before listening on port {}", port),
+        }),
+        ReadyState::TimedOut | ReadyState::Pending => Err(StartError::ServerStartTimeout {
+            msg: format!("Server did not answer on port {}", port),
+        }),
+    }
+}
+
+#[tauri::command]
+pub fn get_server_address(state: tauri::State<'_, AppState>) -> Result<ServerAddress, String> {
+    Ok(state.server_config.lock().unwrap().address())
+}
+
+#[tauri::command]
+pub fn get_server_logs(
+    state: tauri::State<'_, AppState>,
+    filter: Option<LogFilter>,
+) -> Result<Vec<LogLine>, String> {
+    Ok(state.server_logs.query(&filter.unwrap_or_default()))
+}
+
+#[tauri::command]
+pub fn get_server_stats(state: tauri::State<'_, AppState>) -> Result<Option<ServerStats>, String> {
+    Ok(state
         .server
         .lock()
-        .map_err(|err| StartError::ServerStartFailed {
-            msg: format!("Failed to lock server mutex: {}", err),
-        })?;
-    if server.already_started {
-        *server_mutex = Some(server);
-        Ok(StartResult::AlreadyRunning { token })
-    } else {
-        server
-            .start()
-            .map_err(|err| StartError::ServerEnsureError { reason: err })?;
-
-        *server_mutex = Some(server);
-        Ok(StartResult::Starting { token })
+        .unwrap()
+        .as_ref()
+        .and_then(Server::stats))
+}
+
+#[tauri::command]
+pub fn get_launch_options(state: tauri::State<'_, AppState>) -> Result<LaunchOptions, String> {
+    Ok(state.server_config.lock().unwrap().launch.clone())
+}
+
+/// Stores launch options for the next start, or the next restart of a
+/// running server.
+#[tauri::command]
+pub fn set_launch_options(
+    state: tauri::State<'_, AppState>,
+    options: LaunchOptions,
+) -> Result<(), String> {
+    options.validate()?;
+    info!("Setting launch options: {}", options.describe());
+    state
+        .update_server_config(|config| config.launch = options.clone())
+        .map_err(|err| format!("Failed to store server config: {}", err))?;
+    if let Some(server) = state.server.lock().unwrap().as_ref() {
+        server.set_launch_options(options);
     }
+    Ok(())
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -131,14 +177,31 @@ pub enum StopProgress {
 
 #[tauri::command]
 pub async fn stop_server(
-    window: tauri::Window,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
 ) -> Result<(), StopError> {
+    stop(&app, &state)
+}
+
+/// Stops the server, whether this dashboard started it or not.
+pub fn stop(app: &tauri::AppHandle, state: &AppState) -> Result<(), StopError> {
+    let emitter = app.clone();
     let on_progress = move |progress: StopProgress| {
         info!("{:?}", progress);
-        window.emit("stop_progress", progress).unwrap();
+        emitter.emit("stop_progress", progress).unwrap();
     };
 
+    on_progress(StopProgress::ServerStopping {
+        msg: "Stopping server".to_string(),
+    });
+    let stopped = state
+        .stop_owned_server()
+        .map_err(|err| StopError::ServerEnsureError { reason: err })?;
+    if stopped {
+        return Ok(());
+    }
+
+    // The server was not started by this dashboard, stop it through omuserver.
     let options = &state.options;
     let python = {
         let callback = on_progress.clone();
@@ -147,15 +210,10 @@ pub async fn stop_server(
         })
         .map_err(|err| StopError::PythonEnsureError { reason: err })?
     };
-
-    on_progress(StopProgress::ServerStopping {
-        msg: "Stopping server".to_string(),
-    });
-    Server::stop_server(&python, &state.server_config).map_err(|err| {
-        StopError::ServerEnsureError {
-            reason: ServerEnsureError::StopFailed { msg: err },
-        }
-    })?;
+    let python = Server::active_environment(&python);
+    let server_config = state.server_config.lock().unwrap().clone();
+    Server::stop_server(&python, &server_config)
+        .map_err(|err| StopError::ServerEnsureError { reason: err })?;
     Ok(())
 }
 
@@ -163,7 +221,7 @@ pub async fn stop_server(
 #[serde(tag = "type")]
 pub enum CleanError {
     PythonError { reason: PythonEnsureError },
-    ServerError { reason: String },
+    ServerError { reason: ServerEnsureError },
     RemovePythonError { reason: String },
     RemoveUvError { reason: String },
 }
@@ -178,12 +236,18 @@ pub enum CleanProgress {
 
 #[tauri::command]
 pub async fn clean_environment(
-    window: tauri::Window,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
 ) -> Result<(), CleanError> {
+    clean(&app, &state)
+}
+
+/// Stops the server and removes the Python and uv installations.
+pub fn clean(app: &tauri::AppHandle, state: &AppState) -> Result<(), CleanError> {
+    let emitter = app.clone();
     let on_progress = move |progress: CleanProgress| {
         info!("{:?}", progress);
-        window.emit("clean_progress", progress).unwrap();
+        emitter.emit("clean_progress", progress).unwrap();
     };
 
     let options = &state.options;
@@ -194,8 +258,13 @@ pub async fn clean_environment(
         })
         .map_err(|err| CleanError::PythonError { reason: err })?
     };
+    let python = Server::active_environment(&python);
+    let server_config = state.server_config.lock().unwrap().clone();
 
-    Server::stop_server(&python, &state.server_config)
+    state
+        .stop_owned_server()
+        .map_err(|err| CleanError::ServerError { reason: err })?;
+    Server::stop_server(&python, &server_config)
         .map_err(|err| CleanError::ServerError { reason: err })?;
 
     let callback = on_progress.clone();
@@ -238,16 +307,18 @@ pub enum UninstallProgress {
     AppDataRemoving { progress: Progress },
     PythonRemoving { progress: Progress },
     UvRemoving { progress: Progress },
+    UvCacheRemoving { progress: Progress },
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "type")]
 pub enum UninstallError {
     PythonError { reason: PythonEnsureError },
-    ServerError { reason: String },
+    ServerError { reason: ServerEnsureError },
     RemoveAppDataError { reason: String },
     RemovePythonError { reason: String },
     RemoveUvError { reason: String },
+    RemoveUvCacheError { reason: String },
 }
 
 #[tauri::command]
@@ -268,12 +339,17 @@ pub async fn uninstall(
         })
         .map_err(|err| UninstallError::PythonError { reason: err })?
     };
+    let python = Server::active_environment(&python);
+    let server_config = state.server_config.lock().unwrap().clone();
 
-    Server::stop_server(&python, &state.server_config)
+    state
+        .stop_owned_server()
+        .map_err(|err| UninstallError::ServerError { reason: err })?;
+    Server::stop_server(&python, &server_config)
         .map_err(|err| UninstallError::ServerError { reason: err })?;
 
     on_progress(UninstallProgress::PluginRemoving {});
-    Server::uninstall(&python, &state.server_config)
+    Server::uninstall(&python, &server_config)
         .map_err(|err| UninstallError::ServerError { reason: err })?;
 
     let callback = on_progress.clone();
@@ -316,6 +392,20 @@ pub async fn uninstall(
     .map_err(|err| UninstallError::RemoveUvError {
         reason: err.to_string(),
     })?;
+    let uv_cache_dir = options.get_uv_cache_dir();
+    let callback = on_progress.clone();
+    remove_dir_all(&uv_cache_dir, |current, total| {
+        callback(UninstallProgress::UvCacheRemoving {
+            progress: Progress {
+                msg: format!("Removing uv cache at {}", uv_cache_dir.display()),
+                progress: current,
+                total,
+            },
+        });
+    })
+    .map_err(|err| UninstallError::RemoveUvCacheError {
+        reason: err.to_string(),
+    })?;
 
     info!("Environment cleaned");
     Ok(())
@@ -0,0 +1,26 @@
+use std::sync::{Arc, Mutex};
+
+use log::warn;
+use omuapps_core::events::{EventSink, ServerEvent};
+use tauri::{AppHandle, Emitter};
+
+/// Forwards server events to the webview once the app is set up.
+pub struct TauriEvents {
+    app_handle: Arc<Mutex<Option<AppHandle>>>,
+}
+
+impl TauriEvents {
+    pub fn new(app_handle: Arc<Mutex<Option<AppHandle>>>) -> Self {
+        Self { app_handle }
+    }
+}
+
+impl EventSink for TauriEvents {
+    fn emit(&self, event: ServerEvent) {
+        if let Some(app) = self.app_handle.lock().unwrap().as_ref() {
+            if let Err(err) = app.emit(event.name(), &event) {
+                warn!("Failed to emit {} event: {}", event.name(), err);
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use log::{error, info, warn, LevelFilter, Log, Metadata, Record};
+
+use omuapps_core::{
+    events::NoEvents,
+    logs::{ServerLogs, SERVER_LOG_LINES},
+    options::{AppConfig, AppOptions},
+    server::{ReadyState, Server, ServerConfig, READY_WAIT_TIMEOUT},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Set from the signal handler once the service manager asks us to stop.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+struct StdoutLogger;
+
+impl Log for StdoutLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            println!(
+                "{} {:<5} {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+#[cfg(unix)]
+extern "C" fn request_shutdown(_: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn install_signal_handlers() {
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    unsafe {
+        let handler = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+/// Without a handler on Windows, Ctrl+C ends the dashboard and leaves the
+/// server running for the next start to attach to.
+#[cfg(not(unix))]
+fn install_signal_handlers() {}
+
+/// Provisions and supervises the server without a webview, logging to stdout.
+///
+/// Returns the exit code: `0` when stopped by SIGTERM or SIGINT, `1` when the
+/// server could not be started or stopped on its own, so that a service
+/// manager can restart us.
+pub fn run(options: AppOptions, app_config: AppConfig, server_config: ServerConfig) -> i32 {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+    install_signal_handlers();
+    info!("Running in headless mode");
+
+    let server_config = Mutex::new(server_config);
+    let server = match Server::ensure_and_start(
+        &options,
+        &app_config,
+        &server_config,
+        false,
+        |progress| info!("{:?}", progress),
+        Arc::new(NoEvents),
+        Arc::new(ServerLogs::new(SERVER_LOG_LINES)),
+    ) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("Failed to start server: {:?}", err);
+            return 1;
+        }
+    };
+    let port = server_config.lock().unwrap().port;
+    if !server.already_started {
+        match server.readiness().wait(READY_WAIT_TIMEOUT) {
+            ReadyState::Ready => info!("Server is ready on port {}", port),
+            state => warn!("Server is not ready yet: {:?}", state),
+        }
+    } else if !server.is_running() {
+        error!(
+            "A server not started by the dashboard is running on port {}",
+            port
+        );
+        return 1;
+    }
+
+    while !SHUTDOWN.load(Ordering::SeqCst) {
+        if !server.is_running() {
+            error!("Server stopped, exiting");
+            return 1;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    info!("Shutting down");
+    match server.stop() {
+        Ok(_) => 0,
+        Err(err) => {
+            error!("Failed to stop server: {:?}", err);
+            1
+        }
+    }
+}
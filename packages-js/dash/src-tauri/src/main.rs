@@ -1,45 +1,38 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod cli;
 mod commands;
-mod options;
-mod python;
-mod server;
-mod sources;
-mod sync;
-mod utils;
-mod uv;
-mod version;
-
-use crate::{
-    commands::*,
-    options::AppOptions,
-    server::{Server, ServerConfig},
+mod events;
+mod headless;
+
+use crate::{commands::*, events::TauriEvents};
+use log::{info, warn};
+use omuapps_core::{
+    events::EventSink,
+    logs::{ServerLogs, SERVER_LOG_LINES},
+    options::{AppConfig, AppOptions, ServerLifecycle},
+    server::{Server, ServerConfig, ServerEnsureError},
 };
-use directories::ProjectDirs;
-use log::info;
-use once_cell::sync::Lazy;
-use options::AppConfig;
 use serde_json::Value;
 use std::{
     env,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 use tauri::{Emitter, Manager};
 use tauri_plugin_cli::CliExt;
 use tauri_plugin_log::{Target, TargetKind};
 
-static APP_DIRECTORY: Lazy<ProjectDirs> =
-    Lazy::new(|| match ProjectDirs::from("com", "OMUAPPS", "Dashboard") {
-        Some(proj_dirs) => proj_dirs,
-        None => panic!("Failed to get project directories!"),
-    });
-
 struct AppState {
     options: AppOptions,
     config: Arc<Mutex<AppConfig>>,
-    server_config: ServerConfig,
+    server_config: Arc<Mutex<ServerConfig>>,
     server: Arc<Mutex<Option<Server>>>,
+    /// Held for the whole of a start, see [`commands::start`].
+    starting: Mutex<()>,
+    server_logs: Arc<ServerLogs>,
+    events: Arc<dyn EventSink>,
     app_handle: Arc<Mutex<Option<tauri::AppHandle>>>,
 }
 
@@ -47,7 +40,24 @@ impl AppState {
     pub fn update_config(&self, f: impl FnOnce(&mut AppConfig)) {
         let mut config = self.config.lock().unwrap();
         f(&mut config);
-        config.store(&self.options.appdir).unwrap();
+        config.store(&AppConfig::path(&self.options)).unwrap();
+    }
+
+    /// The cache directory uv should use, `None` to use its global cache.
+    pub fn uv_cache_dir(&self) -> Option<PathBuf> {
+        self.config.lock().unwrap().uv_cache_dir(&self.options)
+    }
+
+    /// Stops the server started by this dashboard, `false` if there is none.
+    pub fn stop_owned_server(&self) -> Result<bool, ServerEnsureError> {
+        match self.server.lock().unwrap().as_ref() {
+            Some(server) => server.stop(),
+            None => Ok(false),
+        }
+    }
+
+    pub fn update_server_config(&self, f: impl FnOnce(&mut ServerConfig)) -> std::io::Result<()> {
+        ServerConfig::update(&self.server_config, &self.options, f)
     }
 }
 
@@ -58,6 +68,9 @@ struct LaunchPayload {
 }
 
 fn main() {
+    if let Some(code) = cli::forward(&env::args().collect::<Vec<_>>()) {
+        std::process::exit(code);
+    }
     let options = AppOptions::ensure()
         .map_err(|err| {
             eprintln!("Failed to ensure app options: {}", err);
@@ -67,13 +80,19 @@ fn main() {
     let app_config = AppConfig::ensure(&options);
     let mut server_config = ServerConfig::ensure(&options);
     server_config.workdir = options.workdir.clone();
+    if env::args().skip(1).any(|arg| arg == "--headless") {
+        std::process::exit(headless::run(options, app_config, server_config));
+    }
     let app_handle = Arc::new(Mutex::new(None));
     let app_state = AppState {
         options: options.clone(),
         server: Arc::new(Mutex::new(None)),
+        starting: Mutex::new(()),
+        server_logs: Arc::new(ServerLogs::new(SERVER_LOG_LINES)),
+        events: Arc::new(TauriEvents::new(app_handle.clone())),
         app_handle: app_handle.clone(),
         config: Arc::new(Mutex::new(app_config.clone())),
-        server_config: server_config,
+        server_config: Arc::new(Mutex::new(server_config)),
     };
 
     tauri::Builder::default()
@@ -106,12 +125,20 @@ fn main() {
         .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
             info!("{}, {argv:?}, {cwd}", app.package_info().name);
 
+            if cli::handle(app, argv.clone(), false) {
+                return;
+            }
             app.emit("single-instance", LaunchPayload { args: argv, cwd })
                 .unwrap();
         }))
         .manage(app_state)
         .invoke_handler(tauri::generate_handler![
             start_server,
+            get_server_address,
+            get_server_logs,
+            get_server_stats,
+            get_launch_options,
+            set_launch_options,
             stop_server,
             clean_environment,
             uninstall,
@@ -122,7 +149,14 @@ fn main() {
             get_cookies,
             generate_log_file,
             open_python_path,
-            open_uv_path
+            open_uv_path,
+            list_packages,
+            install_package,
+            upgrade_package,
+            remove_package,
+            sideload_package,
+            get_uv_cache,
+            prune_uv_cache
         ])
         .on_window_event(|window, event| match event {
             tauri::WindowEvent::CloseRequested { api, .. } => {
@@ -153,8 +187,34 @@ fn main() {
                 Err(_) => {}
             }
 
+            // Started by `cli::forward` because no dashboard was running.
+            if cli::handle(app.handle(), env::args().collect(), true) {
+                let window = app.get_webview_window("main").unwrap();
+                window.hide().unwrap();
+            }
+
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                shutdown_server(app);
+            }
+        });
+}
+
+/// Applies the [`ServerLifecycle`] once the dashboard exits, whether from the
+/// tray, the process plugin or a restart for an update.
+fn shutdown_server(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let lifecycle = state.config.lock().unwrap().server_lifecycle;
+    if lifecycle == ServerLifecycle::KeepRunning {
+        info!("Leaving server running");
+        return;
+    }
+    info!("Stopping server on exit");
+    if let Err(err) = state.stop_owned_server() {
+        warn!("Failed to stop server on exit: {:?}", err);
+    }
 }
@@ -1,2 +0,0 @@
-pub(crate) mod py;
-pub(crate) mod uv;
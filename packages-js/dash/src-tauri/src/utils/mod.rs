@@ -1,4 +0,0 @@
-pub(crate) mod archive;
-pub(crate) mod checksum;
-pub(crate) mod download;
-pub(crate) mod filesystem;
@@ -23,6 +23,16 @@ const address: Address = {
     secure: false,
 };
 
+/** Follows the server to the port it was moved to when the default one was taken. */
+export function setServerPort(port: number) {
+    address.port = port;
+}
+
+export async function syncServerAddress() {
+    const { port } = await invoke('get_server_address');
+    setServerPort(port);
+}
+
 class DashboardSession implements SessioTokenProvider {
     async get(): Promise<string | undefined> {
         const token = await invoke('get_token');
@@ -1,10 +1,14 @@
 import { relaunch } from '@tauri-apps/plugin-process';
 import { check, Update } from '@tauri-apps/plugin-updater';
 import { BROWSER } from 'esm-env';
-import { dashboard, omu } from './client.js';
+import { dashboard, omu, setServerPort } from './client.js';
+
+export type ServerLifecycle = 'KeepRunning' | 'StopOnExit' | 'DieWithParent';
 
 export type Config = {
     enable_beta: boolean;
+    share_uv_cache: boolean;
+    server_lifecycle: ServerLifecycle;
 };
 
 export type Cookie = {
@@ -32,29 +36,84 @@ export type UvEnsureProgress = SerdeEnum<{
     UpdateRequirements: {
         msg: string;
     };
+    SnapshotRequirements: {
+        msg: string;
+    };
+    RestoreRequirements: {
+        msg: string;
+    };
+    CreateEnvironment: {
+        msg: string;
+    };
+    InstallPackages: {
+        msg: string;
+    };
+    UninstallPackages: {
+        msg: string;
+    };
+    PruneCache: {
+        msg: string;
+    };
+}>;
+
+export type UvFailure = SerdeEnum<{
+    NoMatchingDistribution: { package: string | null };
+    ResolutionConflict: { packages: string[] };
+    NetworkFailure: { url: string | null };
+    PermissionDenied: { path: string | null };
+    DiskFull: { path: string | null };
+    Unknown: {};
 }>;
 
 export type UvEnsureError = SerdeEnum<{
     CleanupOldVersionsFailed: { msg: string };
-    UpdatePipFailed: { msg: string };
-    UpdateRequirementsFailed: { msg: string };
+    UpdatePipFailed: { msg: string; failure: UvFailure };
+    UpdateRequirementsFailed: { msg: string; failure: UvFailure };
+    SnapshotFailed: { msg: string; failure: UvFailure };
+    RestoreFailed: { msg: string; failure: UvFailure };
+    CreateEnvironmentFailed: { msg: string; failure: UvFailure };
     NoDownloadFound: { msg: string };
+    InvalidIndex: { msg: string };
+    ListPackagesFailed: { msg: string; failure: UvFailure };
+    InstallPackagesFailed: { msg: string; failure: UvFailure };
+    UninstallPackagesFailed: { msg: string; failure: UvFailure };
+    CacheFailed: { msg: string; failure: UvFailure };
+    TimedOut: { msg: string };
 }>;
 
+export type UvCacheInfo = {
+    path: string;
+    size: number;
+    shared: boolean;
+};
+
 export type ServerEnsureProgress = SerdeEnum<{
     UpdatingDependencies: { progress: UvEnsureProgress };
+    DependenciesUpToDate: { msg: string };
+    SideloadSkipped: { msg: string };
     ServerStopping: { msg: string };
 }>;
 
+export type RollbackResult = SerdeEnum<{
+    Restored: { msg: string };
+    Failed: { reason: UvEnsureError };
+}>;
+
 export type ServerEnsureError = {
     VersionReadFailed: { msg: string };
     UpdateDependenciesFailed: { reason: UvEnsureError };
+    UpdateFailed: { reason: UvEnsureError; rollback: RollbackResult };
     StopFailed: { msg: string };
+    UninstallFailed: { msg: string };
     TokenReadFailed: { msg: string };
     TokenWriteFailed: { msg: string };
     CreateDataDirFailed: { msg: string };
+    CreateEnvironmentFailed: { msg: string };
     StartFailed: { msg: string };
+    PortOccupiedByForeignProcess: { msg: string };
     AlreadyRunning: { msg: string };
+    TimedOut: { msg: string };
+    InvalidLaunchOptions: { msg: string };
 };
 
 export type PythonEnsureError = SerdeEnum<{
@@ -69,6 +128,7 @@ export type PythonEnsureProgress = SerdeEnum<{
 
 export type StartError = SerdeEnum<{
     ServerStartFailed: { msg: string };
+    ServerStartTimeout: { msg: string };
     PythonEnsureError: { reason: PythonEnsureError };
     UvEnsureError: { reason: UvEnsureError };
     ServerEnsureError: { reason: ServerEnsureError };
@@ -83,6 +143,7 @@ export type StartProgress = SerdeEnum<{
 export type StartResult = SerdeEnum<{
     AlreadyRunning: { token: string };
     Starting: { token: string };
+    Ready: { token: string };
 }>;
 
 export type StopError = SerdeEnum< {
@@ -97,7 +158,7 @@ export type StopProgress = SerdeEnum< {
 
 export type CleanError = SerdeEnum<{
     PythonError: { reason: PythonEnsureError };
-    ServerError: { reason: string };
+    ServerError: { reason: ServerEnsureError };
     RemovePythonError: { reason: string };
     RemoveUvError: { reason: string };
 }>;
@@ -114,22 +175,92 @@ export type UninstallProgress = SerdeEnum<{
     AppDataRemoving: { progress: Progress };
     PythonRemoving: { progress: Progress };
     UvRemoving: { progress: Progress };
+    UvCacheRemoving: { progress: Progress };
 }>;
 
 export type UninstallError = SerdeEnum<{
     PythonError: { reason: PythonEnsureError };
-    ServerError: { reason: string };
+    ServerError: { reason: ServerEnsureError };
     RemoveAppDataError: { reason: string };
     RemovePythonError: { reason: string };
     RemoveUvError: { reason: string };
+    RemoveUvCacheError: { reason: string };
+}>;
+
+export type PackageInfo = {
+    name: string;
+    version: string;
+    protected: boolean;
+    requirement: string | null;
+    sideloaded: boolean;
+};
+
+export type PackageError = SerdeEnum<{
+    PythonEnsureError: { reason: PythonEnsureError };
+    UvEnsureError: { reason: UvEnsureError };
+    InvalidRequirement: { msg: string };
+    ProtectedPackage: { msg: string };
+    InvalidDistribution: { msg: string };
+    SideloadFailed: { msg: string };
+    ConfigStoreFailed: { msg: string };
+}>;
+
+export type PackageProgress = SerdeEnum<{
+    Python: { progress: PythonEnsureProgress };
+    Uv: { progress: UvEnsureProgress };
 }>;
 
 export type ServerState = SerdeEnum<{
     ServerStarting: { msg: string };
+    ServerStarted: { msg: string };
+    ServerReady: { msg: string };
+    ServerStartTimeout: { msg: string };
     ServerRestarting: { msg: string };
     ServerStopped: { msg: string };
+    ServerCrashLoop: { msg: string; report: string | null };
 }>;
 
+export interface ServerAddress {
+    host: string;
+    port: number;
+}
+
+export type LogLevel = 'Trace' | 'Debug' | 'Info' | 'Success' | 'Warning' | 'Error' | 'Critical';
+
+export type LogStream = 'Stdout' | 'Stderr';
+
+export interface LogLine {
+    seq: number;
+    time: string;
+    stream: LogStream;
+    level: LogLevel;
+    line: string;
+}
+
+export interface LogFilter {
+    min_level?: LogLevel;
+    stream?: LogStream;
+    contains?: string;
+    since?: number;
+    limit?: number;
+}
+
+export interface LaunchOptions {
+    debug: boolean;
+    extra_args: string[];
+    extra_env: Record<string, string>;
+}
+
+export interface ServerStats {
+    pid: number;
+    cpu_percent: number;
+    rss_bytes: number;
+    threads: number | null;
+    open_files: number | null;
+    uptime_secs: number;
+    warnings: string[];
+}
+
 export interface WebviewMessage {
     label: string;
     message: string;
@@ -140,7 +271,11 @@ type Events = {
     stop_progress: StopProgress;
     clean_progress: CleanProgress;
     uninstall_progress: UninstallProgress;
+    package_progress: PackageProgress;
     server_state: ServerState;
+    server_address: ServerAddress;
+    server_log: LogLine;
+    server_stats: ServerStats;
     server_restart: unknown;
     webview_message: WebviewMessage;
     'single-instance': {
@@ -166,7 +301,17 @@ type Events = {
 
 type Commands = {
     close_window(): void;
-    start_server(): StartResult;
+    start_server(options?: {
+        options?: {
+            force_reinstall?: boolean;
+            wait_ready?: boolean;
+        };
+    }): StartResult;
+    get_server_address(): ServerAddress;
+    get_server_logs(options?: { filter?: LogFilter }): LogLine[];
+    get_server_stats(): ServerStats | null;
+    get_launch_options(): LaunchOptions;
+    set_launch_options(options: { options: LaunchOptions }): void;
     stop_server(): undefined;
     clean_environment(): undefined;
     uninstall(): undefined;
@@ -190,6 +335,13 @@ type Commands = {
     clean_environment(): void;
     open_python_path(): void;
     open_uv_path(): void;
+    list_packages(): PackageInfo[];
+    install_package(options: { requirement: string }): void;
+    upgrade_package(options: { name: string }): void;
+    remove_package(options: { name: string }): void;
+    sideload_package(options: { path: string }): void;
+    get_uv_cache(): UvCacheInfo;
+    prune_uv_cache(): UvCacheInfo;
 };
 
 declare module '@tauri-apps/api/core' {
@@ -262,6 +414,9 @@ async function load() {
     await listen('server_state', ({ payload }) => {
         serverState.set(payload);
     });
+    await listen('server_address', ({ payload }) => {
+        setServerPort(payload.port);
+    });
     await listen('webview_message', ({ payload }) => {
         dashboard.processWebviewMessage(payload);
     });